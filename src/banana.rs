@@ -1,6 +1,6 @@
 use rand::Rng;
 use crate::checker::length_range;
-use crate::geom::segment_in_poly;
+use crate::geom::{segment_in_poly, segment_outside_dist2};
 use crate::prelude::*;
 use crate::shake::ShakeRequest;
 
@@ -16,17 +16,20 @@ pub fn banana_shake(r: &ShakeRequest) -> Vec<Pt> {
             if !selected[start] || !selected[end] {
                 continue;
             }
+            let orig_d = r.problem.figure.vertices[start].dist2(r.problem.figure.vertices[end]);
+            // zero-length edges count as length 1, the penalties are relative to it
+            let scale = orig_d.max(1) as f64;
             if !segment_in_poly((vs[start], vs[end]), &r.problem.hole) {
-                score -= 1.0;
+                let outside = segment_outside_dist2((vs[start], vs[end]), &r.problem.hole);
+                score -= 1.0 + outside.to_f64() / scale;
             }
-            let orig_d = r.problem.figure.vertices[start].dist2(r.problem.figure.vertices[end]);
             let (min_d, max_d, _) = length_range(orig_d, r.problem.epsilon);
             let d = vs[start].dist2(vs[end]);
             if d < min_d {
-                score -= (min_d - d) as f64 / orig_d as f64;
+                score -= (min_d - d) as f64 / scale;
             }
             if d > max_d {
-                score -= (d - max_d) as f64 / orig_d as f64;
+                score -= (d - max_d) as f64 / scale;
             }
         }
         score
@@ -42,10 +45,15 @@ pub fn banana_shake(r: &ShakeRequest) -> Vec<Pt> {
         }
     }
 
-    let mut rng = rand::thread_rng();
+    let mut rng = r.rng();
     let mut cur = r.vertices.clone();
     let mut cur_score = compute_score(&cur);
-    for _ in 0..1000 {
+    for i in 0..1000 {
+        if r.stop.should_stop() {
+            break;
+        }
+        // scores are penalties, negated
+        r.progress.report(i, Some(-cur_score), Some(-best_score), None);
         let old = cur.clone();
         let old_score = cur_score;
         for _ in 0..10 * r.param {
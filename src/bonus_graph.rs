@@ -15,7 +15,7 @@ fn bonus_graph() {
     writeln!(fout, "  legend_z -> legend_t [label=WALLHACK,color=red];").unwrap();
     writeln!(fout, "  legend_t -> legend_u [label=SUPERFLEX,color=yellow];").unwrap();
 
-    for problem_no in all_problem_ids() {
+    for problem_no in crate::registry::selected_problem_ids() {
         let p = load_problem(problem_no);
         print!("{} -> ", problem_no);
 
@@ -10,10 +10,12 @@ use crate::mango::available_positions;
 use rand::prelude::SliceRandom;
 use crate::checker::{get_dislikes, Checker};
 use std::collections::HashSet;
+use crate::cancel::CancelToken;
 
 struct Meta {
     max_available_positions: usize,
     latest_in: usize,
+    stop: CancelToken,
 }
 
 fn go(checker: &mut Checker,
@@ -22,6 +24,9 @@ fn go(checker: &mut Checker,
       places: &mut Vec<Option<Pt>>,
       meta: &mut Meta) -> Option<Vec<Pt>> {
 
+    if meta.stop.should_stop() {
+        return None;
+    }
     if offset < order.len() {
         let current_id = order[offset];
 
@@ -67,29 +72,34 @@ fn go(checker: &mut Checker,
     }
 }
 
-fn brutforce_with(checker: &mut Checker, v_id: usize, pt: Pt) -> Option<Vec<Pt>> {
+fn brutforce_with(checker: &mut Checker, v_id: usize, pt: Pt, stop: &CancelToken) -> Option<Vec<Pt>> {
     let mut order = bfs(&checker.problem.figure.edges, v_id)[1..].to_vec();
     let mut places: Vec<Option<Pt>> = vec![None; checker.problem.figure.vertices.len()];
     places[v_id] = Some(pt);
-    go(checker, &order, 0, &mut places, &mut Meta { max_available_positions: 0, latest_in: 100500 })
+    go(checker, &order, 0, &mut places, &mut Meta { max_available_positions: 0, latest_in: 100500, stop: stop.clone() })
 }
 
 pub fn brutforce(r: &ShakeRequest) -> Vec<Pt> {
-    let rng = &mut rand::thread_rng();
+    let rng = &mut r.rng();
     let mut v_ids: Vec<_> = (0..r.vertices.len()).into_iter().collect();
     v_ids.shuffle(rng);
     let mut h_pts = r.problem.hole.clone();
     h_pts.shuffle(rng);
     let mut checker = Checker::new(&r.problem, &[], r.problem.figure.vertices.len());
+    // one event per (vertex, hole vertex) pair tried
+    let mut tried = 0;
     for v_id in v_ids {
-        for (h_id, pt) in h_pts.iter().enumerate() {
-            eprintln!("Trying: v_id {:?} h_id {:?}", v_id, h_id);
-            if let Some(result) = brutforce_with(&mut checker, v_id, *pt) {
-                eprintln!("Success!");
+        for pt in &h_pts {
+            if r.stop.should_stop() {
+                return r.vertices.clone();
+            }
+            let result = brutforce_with(&mut checker, v_id, *pt, &r.stop);
+            r.progress.report(tried, None, None, result.as_ref().map(|_| 0));
+            tried += 1;
+            if let Some(result) = result {
                 return result;
             }
         }
     }
-    eprintln!("No luck so far!");
     r.vertices.clone()
 }
\ No newline at end of file
@@ -0,0 +1,84 @@
+// Stopping long-running shakes and solvers from outside.
+// They check the token in their inner loops and return the best pose so far.
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+    // StopReason as u8, set when should_stop() first says yes
+    stopped: Arc<AtomicU8>,
+}
+
+#[derive(serde::Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Converged,  // or ran its course
+    TimedOut,
+    Cancelled,
+}
+
+impl CancelToken {
+    pub fn with_time_limit(time_limit: Option<Duration>) -> CancelToken {
+        CancelToken { deadline: time_limit.map(|t| Instant::now() + t), ..Default::default() }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Stops everything holding this token or its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn should_stop(&self) -> bool {
+        let reason = if self.cancelled.load(Ordering::Relaxed) {
+            StopReason::Cancelled
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            StopReason::TimedOut
+        } else {
+            return false;
+        };
+        // the first reason seen sticks
+        let _ = self.stopped.compare_exchange(
+            StopReason::Converged as u8, reason as u8, Ordering::Relaxed, Ordering::Relaxed);
+        true
+    }
+
+    /// Why a method that has returned stopped. Converged unless it saw
+    /// should_stop() say yes, even if the deadline has passed since.
+    pub fn stop_reason(&self) -> StopReason {
+        match self.stopped.load(Ordering::Relaxed) {
+            r if r == StopReason::Cancelled as u8 => StopReason::Cancelled,
+            r if r == StopReason::TimedOut as u8 => StopReason::TimedOut,
+            _ => StopReason::Converged,
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_cancel_token() {
+    let t = CancelToken::default();
+    assert!(!t.should_stop());
+    let t2 = t.clone();
+    t2.cancel();
+    // nobody has looked yet
+    assert_eq!(t.stop_reason(), StopReason::Converged);
+    assert!(t.should_stop());
+    assert_eq!(t.stop_reason(), StopReason::Cancelled);
+    assert_eq!(t2.stop_reason(), StopReason::Cancelled);
+
+    let t = CancelToken::with_time_limit(Some(Duration::from_millis(0)));
+    assert_eq!(t.stop_reason(), StopReason::Converged);
+    assert!(t.should_stop());
+    t.cancel();
+    assert!(t.should_stop());
+    assert_eq!(t.stop_reason(), StopReason::TimedOut);
+    assert_eq!(CancelToken::with_time_limit(Some(Duration::from_secs(100))).stop_reason(), StopReason::Converged);
+}
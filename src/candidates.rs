@@ -0,0 +1,56 @@
+// Candidate positions of a figure vertex given its placed neighbours:
+// annuli of allowed edge lengths intersected with the rasterized hole,
+// then restricted to the points from which every neighbour is visible.
+
+use crate::prelude::*;
+use crate::hole_index::HoleIndex;
+use crate::rle::Set2D;
+use crate::visibility::visible_from;
+
+/// Lattice points p of the hole such that for every (n, min_d, max_d)
+/// min_d <= p.dist2(n) <= max_d and segment_in_poly((n, p), hole).
+/// inside is Set2D::from_polygon(hole).
+pub fn candidates(index: &HoleIndex, inside: &Set2D, neighbours: &[(Pt, i64, i64)]) -> Set2D {
+    let mut result = inside.clone();
+    for &(n, min_d, max_d) in neighbours {
+        result = result.intersection(&Set2D::ring(n.x, n.y, min_d, max_d));
+    }
+    for &(n, _, _) in neighbours {
+        let bbox = match result.bbox() {
+            Some(bbox) => bbox,
+            None => break,
+        };
+        result = result.intersection(&visible_from(index, n, bbox));
+    }
+    result
+}
+
+#[cfg(test)]
+#[test]
+fn test_candidates() {
+    use crate::geom::{pt_in_poly, segment_in_poly};
+
+    let hole = load_problem(9).hole;
+    let index = HoleIndex::new(&hole);
+    let inside = Set2D::from_polygon(&hole);
+    let b = index.bbox;
+    let cases = [
+        vec![],
+        vec![(hole[0], 100, 400)],
+        vec![(hole[3], 0, 2000), (Pt::new((b.min_x + b.max_x) / 2, (b.min_y + b.max_y) / 2), 300, 900)],
+        vec![(hole[1], 50, 3000), (hole[2], 50, 3000), (hole[5], 0, 10000)],
+    ];
+    for neighbours in &cases {
+        let result = candidates(&index, &inside, neighbours);
+        for x in b.min_x - 1..=b.max_x + 1 {
+            for y in b.min_y - 1..=b.max_y + 1 {
+                let pt = Pt::new(x, y);
+                let expected = pt_in_poly(pt, &hole) && neighbours.iter().all(|&(n, min_d, max_d)| {
+                    let d = pt.dist2(n);
+                    min_d <= d && d <= max_d && segment_in_poly((n, pt), &hole)
+                });
+                assert_eq!(result.contains(pt), expected, "{:?} {:?}", neighbours, pt);
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Problems up to translation and the 8 lattice symmetries.
+//
+// Holes and figures are brought to a canonical frame separately: of the
+// 8 images with the bounding box moved to the origin we take the smallest.
+// For holes the starting vertex and the direction don't matter either.
+// Figures keep their vertex numbering, so figures count as the same only
+// when they are numbered the same way, which is what it takes to carry
+// a pose over. Bonuses are ignored.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use crate::prelude::*;
+use crate::checker::check_pose;
+use crate::geom::{BBox, Transform};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CanonicalFigure {
+    pub vertices: Vec<Pt>,
+    pub edges: Vec<(usize, usize)>,  // (smaller, larger), sorted
+}
+
+// Every lattice symmetry followed by the translation of the bbox corner to the origin.
+fn frames(pts: &[Pt]) -> Vec<(Transform, Vec<Pt>)> {
+    Transform::lattice_symmetries().into_iter().map(|t| {
+        let image: Vec<Pt> = pts.iter().map(|&p| t.apply(p).unwrap()).collect();
+        let b = if image.is_empty() { BBox { min_x: 0, max_x: 0, min_y: 0, max_y: 0 } } else { BBox::from_pts(&image) };
+        let t = Transform::translation(Pt::new(-b.min_x, -b.min_y)).compose(&t);
+        let image = pts.iter().map(|&p| t.apply(p).unwrap()).collect();
+        (t, image)
+    }).collect()
+}
+
+fn key(pts: &[Pt]) -> Vec<(i64, i64)> {
+    pts.iter().map(|&p| p.into()).collect()
+}
+
+/// The figure in its canonical frame and the transform that takes it there.
+pub fn canonical_figure(figure: &Figure) -> (CanonicalFigure, Transform) {
+    let mut edges: Vec<(usize, usize)> = figure.edges.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect();
+    edges.sort_unstable();
+    let (t, vertices) = frames(&figure.vertices).into_iter()
+        .min_by_key(|(_, image)| key(image))
+        .unwrap();
+    (CanonicalFigure { vertices, edges }, t)
+}
+
+// Smallest rotation of the cycle in either direction.
+fn canonical_cycle(pts: &[Pt]) -> Vec<Pt> {
+    let n = pts.len();
+    let mut best: Option<Vec<Pt>> = None;
+    for start in 0..n {
+        for &dir in &[1, n - 1] {
+            let cand: Vec<Pt> = (0..n).map(|i| pts[(start + i * dir) % n]).collect();
+            if best.as_ref().is_none_or(|best| key(&cand) < key(best)) {
+                best = Some(cand);
+            }
+        }
+    }
+    best.unwrap_or_default()
+}
+
+/// The hole in its canonical frame, starting vertex and direction
+/// normalized too, and the transform that takes it there.
+pub fn canonical_hole(hole: &[Pt]) -> (Vec<Pt>, Transform) {
+    frames(hole).into_iter()
+        .map(|(t, image)| (canonical_cycle(&image), t))
+        .min_by_key(|(cycle, _)| key(cycle))
+        .unwrap()
+}
+
+#[derive(Clone, Debug)]
+pub struct CanonicalProblem {
+    pub hole: Vec<Pt>,
+    pub hole_transform: Transform,
+    pub figure: CanonicalFigure,
+    pub epsilon: i64,
+}
+
+fn fingerprint(x: impl Hash) -> u64 {
+    let mut h = DefaultHasher::new();
+    x.hash(&mut h);
+    h.finish()
+}
+
+impl CanonicalProblem {
+    pub fn new(problem: &Problem) -> CanonicalProblem {
+        let (hole, hole_transform) = canonical_hole(&problem.hole);
+        let (figure, _) = canonical_figure(&problem.figure);
+        CanonicalProblem { hole, hole_transform, figure, epsilon: problem.epsilon }
+    }
+
+    pub fn hole_fingerprint(&self) -> u64 {
+        fingerprint(&self.hole)
+    }
+
+    pub fn figure_fingerprint(&self) -> u64 {
+        fingerprint(&self.figure)
+    }
+
+    /// Same for problems that are the same up to translation and symmetry.
+    pub fn fingerprint(&self) -> u64 {
+        fingerprint((&self.hole, &self.figure, self.epsilon))
+    }
+
+    /// Can poses be carried over from one to the other, see map_pose().
+    pub fn same_hole_and_figure(&self, other: &CanonicalProblem) -> bool {
+        self.hole == other.hole && self.figure == other.figure
+    }
+}
+
+/// The pose for problem `from` moved to problem `to`, if they have the same
+/// hole and figure. Dislikes stay the same; validity too, unless `to` has
+/// a smaller epsilon. Bonuses are dropped, they belong to the old problem.
+pub fn map_pose(pose: &Pose, from: &CanonicalProblem, to: &CanonicalProblem) -> Option<Pose> {
+    if !from.same_hole_and_figure(to) {
+        return None;
+    }
+    let t = to.hole_transform.inverse().compose(&from.hole_transform);
+    let vertices = pose.vertices.iter().map(|&p| t.apply(p).unwrap()).collect();
+    Some(Pose { vertices, bonuses: vec![] })
+}
+
+fn read_solution(problem_id: i32) -> Option<Pose> {
+    let data = std::fs::read(project_path(format!("outputs/sol_{}.json", problem_id))).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+// usage: cargo run twins [--problems ...] [--write]
+// Lists problems that are the same or share a hole or a figure. Where they share both, carries
+// solutions from outputs/sol_*.json over to the twins (epsilon may differ,
+// check_pose decides), with --write stores them where they are better than what's there.
+crate::entry_point!("twins", twins);
+fn twins() {
+    let write = std::env::args().any(|a| a == "--write");
+    let problems: Vec<(i32, Problem, CanonicalProblem)> = crate::registry::selected_problem_ids().into_iter()
+        .map(|id| {
+            let p = load_problem(id);
+            let c = CanonicalProblem::new(&p);
+            (id, p, c)
+        })
+        .collect();
+
+    let mut by_hole: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    let mut by_figure: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    let mut by_problem: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    let mut by_hole_and_figure: BTreeMap<(u64, u64), Vec<usize>> = BTreeMap::new();
+    for (i, (_, _, c)) in problems.iter().enumerate() {
+        by_hole.entry(c.hole_fingerprint()).or_default().push(i);
+        by_figure.entry(c.figure_fingerprint()).or_default().push(i);
+        by_problem.entry(c.fingerprint()).or_default().push(i);
+        by_hole_and_figure.entry((c.hole_fingerprint(), c.figure_fingerprint())).or_default().push(i);
+    }
+    let ids = |group: &[usize]| group.iter().map(|&i| problems[i].0.to_string()).collect::<Vec<_>>().join(", ");
+    for group in by_hole.values().filter(|g| g.len() > 1) {
+        println!("same hole: {}", ids(group));
+    }
+    for group in by_figure.values().filter(|g| g.len() > 1) {
+        println!("same figure: {}", ids(group));
+    }
+    for group in by_problem.values().filter(|g| g.len() > 1) {
+        println!("same problem: {}", ids(group));
+    }
+    for group in by_hole_and_figure.values().filter(|g| g.len() > 1) {
+        let epsilons = group.iter().map(|&i| problems[i].1.epsilon.to_string()).collect::<Vec<_>>().join(", ");
+        println!("same hole and figure: {} (epsilon {})", ids(group), epsilons);
+        for &to in group {
+            let (to_id, to_problem, to_c) = &problems[to];
+            let mut current = read_solution(*to_id)
+                .map(|pose| check_pose(to_problem, &pose))
+                .filter(|check| check.valid)
+                .map(|check| check.dislikes);
+            for &from in group.iter().filter(|&&from| from != to) {
+                let (from_id, _, from_c) = &problems[from];
+                let pose = match read_solution(*from_id).and_then(|pose| map_pose(&pose, from_c, to_c)) {
+                    Some(pose) => pose,
+                    None => continue,
+                };
+                let check = check_pose(to_problem, &pose);
+                if !check.valid {
+                    continue;
+                }
+                println!("    {} -> {}: {} dislikes, had {:?}", from_id, to_id, check.dislikes, current);
+                if write && current.is_none_or(|d| check.dislikes < d) {
+                    crate::util::store_solution(*to_id, &pose);
+                    current = Some(check.dislikes);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_canonical() {
+    let p = load_problem(11);
+    let c = CanonicalProblem::new(&p);
+    for (i, t) in Transform::lattice_symmetries().into_iter().enumerate() {
+        let t = Transform::translation(Pt::new(17, -5 * i as i64)).compose(&t);
+        let mut hole: Vec<Pt> = p.hole.iter().map(|&pt| t.apply(pt).unwrap()).collect();
+        let n = hole.len();
+        hole.rotate_left(i % n);
+        if i % 2 == 1 {
+            hole.reverse();
+        }
+        let vertices = p.figure.vertices.iter().map(|&pt| t.apply(pt).unwrap() + Pt::new(3, 3)).collect();
+        let twin = Problem {
+            bonuses: vec![],
+            hole,
+            figure: Figure { vertices, edges: p.figure.edges.iter().map(|&(a, b)| (b, a)).collect() },
+            epsilon: p.epsilon,
+        };
+        let c2 = CanonicalProblem::new(&twin);
+        assert_eq!(c2.fingerprint(), c.fingerprint());
+        assert_eq!(c2.hole, c.hole);
+
+        let pose = Pose { vertices: p.figure.vertices.clone(), bonuses: vec![] };
+        let check = check_pose(&p, &pose);
+        let mapped = map_pose(&pose, &c, &c2).unwrap();
+        let check2 = check_pose(&twin, &mapped);
+        assert_eq!((check.valid, check.dislikes), (check2.valid, check2.dislikes));
+        let back = map_pose(&mapped, &c2, &c).unwrap();
+        assert_eq!(back.vertices, pose.vertices);
+    }
+    let other = CanonicalProblem::new(&load_problem(12));
+    assert_ne!(other.fingerprint(), c.fingerprint());
+    assert!(map_pose(&Pose { vertices: vec![], bonuses: vec![] }, &c, &other).is_none());
+}
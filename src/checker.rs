@@ -2,8 +2,11 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use crate::domain_model::{BonusName, UnlockedBonus};
 use crate::prelude::*;
-use crate::geom::{pt_in_poly, segment_in_poly, BBox};
+use crate::geom::MAX_COORD;
+use crate::hole_index::HoleIndex;
+use crate::rle::Set2D;
 use crate::graph::neighbours;
+use crate::nearest::NearestIndex;
 
 #[derive(serde::Deserialize)]
 pub struct CheckPoseRequest {
@@ -18,12 +21,60 @@ pub struct CheckPoseResponse {
     pub edge_statuses: Vec<EdgeStatus>,
     pub dislikes: i64,
     pub valid: bool,
+    pub violations: Vec<Violation>,  // empty iff valid
     pub unlocked: Vec<bool>,
     pub bonus_globalist_sum: Option<f64>
 }
 
+// Why a pose is invalid. Edge indices are those of CheckPoseResponse::edges.
 #[derive(serde::Serialize)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Violation {
+    // the figure's, plus one with BREAK_A_LEG
+    WrongVertexCount { expected: usize, actual: usize },
+    EdgeTooShort { edge: usize, length: i64, min_length: i64 },
+    EdgeTooLong { edge: usize, length: i64, max_length: i64 },
+    VertexOutsideHole { vertex: usize },
+    // Both ends are in the hole, but the edge is not.
+    // hole_edge is the first hole edge it properly crosses, if any.
+    EdgeCrossesHole { edge: usize, hole_edge: Option<usize> },
+    TooManyBonuses { count: usize },
+    InvalidBonus { bonus: BonusName, reason: String },
+    GlobalistLimitExceeded { sum: f64, limit: f64 },
+    // |x| or |y| above MAX_COORD
+    CoordinateOutOfRange { vertex: usize, pt: Pt },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::WrongVertexCount { expected, actual } =>
+                write!(f, "{} vertices instead of {}", actual, expected),
+            Violation::EdgeTooShort { edge, length, min_length } =>
+                write!(f, "edge {} is too short: {} < {} by {}", edge, length, min_length, min_length - length),
+            Violation::EdgeTooLong { edge, length, max_length } =>
+                write!(f, "edge {} is too long: {} > {} by {}", edge, length, max_length, length - max_length),
+            Violation::VertexOutsideHole { vertex } =>
+                write!(f, "vertex {} is outside the hole", vertex),
+            Violation::EdgeCrossesHole { edge, hole_edge: Some(hole_edge) } =>
+                write!(f, "edge {} crosses hole edge {}", edge, hole_edge),
+            Violation::EdgeCrossesHole { edge, hole_edge: None } =>
+                write!(f, "edge {} goes outside the hole", edge),
+            Violation::TooManyBonuses { count } =>
+                write!(f, "{} bonuses used, at most one is allowed", count),
+            Violation::InvalidBonus { bonus, reason } =>
+                write!(f, "invalid {:?}: {}", bonus, reason),
+            Violation::GlobalistLimitExceeded { sum, limit } =>
+                write!(f, "GLOBALIST sum {:.0} exceeds limit {:.0}", sum, limit),
+            Violation::CoordinateOutOfRange { vertex, pt } =>
+                write!(f, "vertex {} at ({}, {}) is out of range", vertex, pt.x, pt.y),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone)]
 pub struct EdgeStatus {
     pub fits_in_hole: bool,
     pub actual_length: i64,
@@ -32,6 +83,13 @@ pub struct EdgeStatus {
     pub max_length: i64,
 }
 
+impl EdgeStatus {
+    pub fn is_violated(&self) -> bool {
+        !self.fits_in_hole ||
+        self.actual_length < self.min_length || self.actual_length > self.max_length
+    }
+}
+
 // inclusive
 pub fn length_range(d: i64, eps: i64) -> (i64, i64, i64) {
     let min_length = (d * (EPS_BASE - eps) + EPS_BASE - 1) / EPS_BASE;
@@ -42,7 +100,8 @@ pub fn length_range(d: i64, eps: i64) -> (i64, i64, i64) {
 // Precomputed data to quickly check pose constraints.
 pub struct Checker {
     pub problem: Problem,
-    pub bbox: BBox,
+    pub hole_index: HoleIndex,
+    pub inside: Set2D,  // lattice points of the hole
     pub edge_ranges: Vec<(i64, i64, i64)>, // min, max, orig_x4
     pub edges: Vec<(usize, usize)>,
     pub edge_cache: HashMap<[i16; 4], bool>,
@@ -62,7 +121,8 @@ impl Checker {
         let mut checker = Checker {
             problem: p.clone(),
             edges: p.figure.edges.clone(),
-            bbox: BBox::from_pts(&p.hole),
+            hole_index: HoleIndex::new(&p.hole),
+            inside: Set2D::from_polygon(&p.hole),
             edge_ranges,
             edge_cache: HashMap::new(),
             neighbours_cache: HashMap::new(),
@@ -100,15 +160,14 @@ impl Checker {
         if (pt1.x, pt1.y) > (pt2.x, pt1.y) {
             std::mem::swap(&mut pt1, &mut pt2);
         }
-        let hole = &self.problem.hole;
-        let key = [
-            pt1.x.try_into().unwrap(),
-            pt1.y.try_into().unwrap(),
-            pt2.x.try_into().unwrap(),
-            pt2.y.try_into().unwrap(),
-        ];
+        let hole_index = &self.hole_index;
+        let key = match (pt1.x.try_into(), pt1.y.try_into(), pt2.x.try_into(), pt2.y.try_into()) {
+            (Ok(x1), Ok(y1), Ok(x2), Ok(y2)) => [x1, y1, x2, y2],
+            // far outside any real hole, not worth caching
+            _ => return hole_index.segment_in_hole((pt1, pt2)),
+        };
         *self.edge_cache.entry(key).or_insert_with(|| {
-            segment_in_poly((pt1, pt2), hole)
+            hole_index.segment_in_hole((pt1, pt2))
         })
     }
 
@@ -128,13 +187,19 @@ pub fn get_dislikes(problem: &Problem, vertices: &[Pt]) -> i64 {
     if vertices.is_empty() {
         return 1_000_000_000;
     }
-    let mut dislikes = 0;
-    for &h in &problem.hole {
-        dislikes += vertices.iter().map(|v| v.dist2(h)).min().unwrap();
+    // Greedy, threshold and bruteforce call it in their inner loops,
+    // building the grid only pays off for big problems.
+    if problem.hole.len() * vertices.len() <= BRUTE_FORCE_DISLIKES {
+        return problem.hole.iter()
+            .map(|&h| vertices.iter().map(|v| v.dist2(h)).min().unwrap())
+            .sum();
     }
-    dislikes
+    let index = NearestIndex::new(vertices);
+    problem.hole.iter().map(|&h| index.nearest(h).unwrap().0).sum()
 }
 
+const BRUTE_FORCE_DISLIKES: usize = 4096;
+
 #[allow(clippy::needless_range_loop)]
 pub fn check_unlocked(problem: &Problem, vertices: &[Pt]) -> Vec<bool> {
     let mut unlocked: Vec<bool> = vec![false; problem.bonuses.len()];
@@ -163,26 +228,38 @@ pub fn list_unlocked_bonuses(problem: &Problem, vertices: &[Pt]) -> Vec<Unlocked
 }
 
 
-#[allow(clippy::needless_range_loop)]
-pub fn check_edges_in_hole(problem: &Problem, pose: &Pose, 
-        edge_statuses: &[EdgeStatus], checker: &Checker) -> bool {
-    let mut wallhack: Option<usize> = None;
-    for i in 0..edge_statuses.len() {
-        if edge_statuses[i].fits_in_hole { continue; }
-        if !used(&checker.bonus, &BonusName::WALLHACK) { return false; }
+// Edges that don't fit in the hole, skipping the ones marked in `skip`.
+// With WALLHACK a single vertex may stick out.
+pub fn hole_violations(pose: &Pose, edge_statuses: &[EdgeStatus], skip: &[bool],
+        checker: &Checker) -> Vec<Violation> {
+    let mut violations = vec![];
+    let mut outside = vec![];
+    for (i, es) in edge_statuses.iter().enumerate() {
+        if es.fits_in_hole || skip[i] { continue; }
         let (v1, v2) = checker.edges[i];
-        let (fit1, fit2) = (pt_in_poly(pose.vertices[v1], &problem.hole),
-                            pt_in_poly(pose.vertices[v2], &problem.hole));
-        if fit1 == fit2 { return false; }
-        let newhack = if fit1 { v2 } else { v1 };
-        match wallhack {
-            None => wallhack = Some(newhack),
-            Some(a) => {
-                if a != newhack { return false; }
-            }
+        let (pt1, pt2) = (pose.vertices[v1], pose.vertices[v2]);
+        let (fit1, fit2) = (checker.hole_index.pt_in_hole(pt1), checker.hole_index.pt_in_hole(pt2));
+        if fit1 && fit2 {
+            let hole_edge = if pt1 == pt2 {
+                None
+            } else {
+                crate::geom::poly_edges(&checker.problem.hole).position(|(h1, h2)| {
+                    crate::geom::segment_intersection((pt1, pt2), (h1, h2)) ==
+                        crate::geom::Intersection::Internal
+                })
+            };
+            violations.push(Violation::EdgeCrossesHole { edge: i, hole_edge });
         }
+        if !fit1 { outside.push(v1); }
+        if !fit2 { outside.push(v2); }
+    }
+    outside.sort_unstable();
+    outside.dedup();
+    let wallhack = used(&checker.bonus, &BonusName::WALLHACK);
+    if !(wallhack && outside.len() == 1) {
+        violations.extend(outside.into_iter().map(|vertex| Violation::VertexOutsideHole { vertex }));
     }
-    true
+    violations
 }
 
 pub fn check_valid_break_a_leg(bonus: &PoseBonus, problem: &Problem, vertex_cnt: usize) -> bool {
@@ -197,92 +274,416 @@ pub fn check_valid_break_a_leg(bonus: &PoseBonus, problem: &Problem, vertex_cnt:
     }
 }
 
-pub fn globalist_sum_len(edge_statuses: &[EdgeStatus]) -> f64 {
-    let mut eps = 0.;
-    for e in edge_statuses {
-        eps += f64::abs(e.actual_length as f64 * 4. / e.original_length_x4 as f64 - 1.); 
+// GLOBALIST deviation of each edge: |4 * actual / original_x4 - 1| * EPS_BASE,
+// as a fraction (numerator, denominator). Skipping the edges marked in `skip`
+// and degenerate figure edges, for which the ratio is undefined.
+fn globalist_terms<'a>(edge_statuses: &'a [EdgeStatus], skip: &'a [bool]) -> impl Iterator<Item = (u128, u64)> + 'a {
+    edge_statuses.iter().zip(skip)
+        .filter(|&(e, &skip)| !skip && e.original_length_x4 > 0)
+        .map(|(e, _)| {
+            let dev = (4 * e.actual_length as i128 - e.original_length_x4 as i128).unsigned_abs();
+            (dev * EPS_BASE as u128, e.original_length_x4 as u64)
+        })
+}
+
+// Skipping the edges marked in `skip`. Only for display and as a search objective,
+// globalist_check_edge_lens() is the exact check.
+pub fn globalist_sum_len(edge_statuses: &[EdgeStatus], skip: &[bool]) -> f64 {
+    globalist_terms(edge_statuses, skip).map(|(num, den)| num as f64 / den as f64).sum()
+}
+
+// Unbounded naturals, little-endian 64-bit limbs. Just enough to sum fractions exactly.
+fn big_mul(x: &[u64], m: u64) -> Vec<u64> {
+    let mut res = Vec::with_capacity(x.len() + 1);
+    let mut carry = 0u128;
+    for &limb in x {
+        let t = limb as u128 * m as u128 + carry;
+        res.push(t as u64);
+        carry = t >> 64;
+    }
+    if carry > 0 {
+        res.push(carry as u64);
+    }
+    res
+}
+
+fn big_add(x: &[u64], y: &[u64]) -> Vec<u64> {
+    let mut res = Vec::with_capacity(x.len().max(y.len()) + 1);
+    let mut carry = 0u128;
+    for i in 0..x.len().max(y.len()) {
+        let t = *x.get(i).unwrap_or(&0) as u128 + *y.get(i).unwrap_or(&0) as u128 + carry;
+        res.push(t as u64);
+        carry = t >> 64;
+    }
+    if carry > 0 {
+        res.push(carry as u64);
+    }
+    res
+}
+
+fn big_cmp(x: &[u64], y: &[u64]) -> std::cmp::Ordering {
+    let significant = |x: &[u64]| x.iter().rposition(|&limb| limb != 0).map_or(0, |i| i + 1);
+    let (x, y) = (&x[..significant(x)], &y[..significant(y)]);
+    x.len().cmp(&y.len()).then_with(|| x.iter().rev().cmp(y.iter().rev()))
+}
+
+// Exact: is the sum of the deviations at most edges * epsilon?
+pub fn globalist_check_edge_lens(problem: &Problem, edge_statuses: &[EdgeStatus], skip: &[bool]) -> bool {
+    let limit = problem.figure.edges.len() as u128 * problem.epsilon.max(0) as u128;
+    // whole parts separately, the fractional ones as num / den
+    let mut whole = 0u128;
+    let mut num = vec![];
+    let mut den = vec![1];
+    for (n, d) in globalist_terms(edge_statuses, skip) {
+        whole += n / d as u128;
+        if whole > limit {
+            return false;
+        }
+        let r = (n % d as u128) as u64;
+        if r > 0 {
+            num = big_add(&big_mul(&num, d), &big_mul(&den, r));
+            den = big_mul(&den, d);
+        }
+    }
+    let rest: u64 = match (limit - whole).try_into() {
+        Ok(rest) => rest,
+        Err(_) => return true,  // more than the fractional parts can add up to
+    };
+    big_cmp(&num, &big_mul(&den, rest)) != std::cmp::Ordering::Greater
+}
+
+// Edges of wrong length, skipping the ones marked in `skip`.
+// With SUPERFLEX a single one is allowed.
+pub fn length_violations(pose: &Pose, edge_statuses: &[EdgeStatus], skip: &[bool]) -> Vec<Violation> {
+    let mut violations = vec![];
+    for (i, e) in edge_statuses.iter().enumerate() {
+        if skip[i] { continue; }
+        if e.actual_length < e.min_length {
+            violations.push(Violation::EdgeTooShort {
+                edge: i, length: e.actual_length, min_length: e.min_length });
+        } else if e.actual_length > e.max_length {
+            violations.push(Violation::EdgeTooLong {
+                edge: i, length: e.actual_length, max_length: e.max_length });
+        }
     }
-    eps * 1e6
+    if violations.len() == 1 && pose.bonuses.iter().any(|b| b.bonus == BonusName::SUPERFLEX) {
+        violations.clear();
+    }
+    violations
 }
 
-pub fn globalist_check_edge_lens(problem: &Problem, sum_eps: f64) -> bool {
-    sum_eps <= problem.figure.edges.len() as f64 * problem.epsilon as f64
+fn bonus_violations(problem: &Problem, pose: &Pose, checker: &Checker) -> Vec<Violation> {
+    let mut violations = vec![];
+    if pose.bonuses.len() > 1 {
+        violations.push(Violation::TooManyBonuses { count: pose.bonuses.len() });
+    }
+    if let Some(b) = &checker.bonus {
+        if b.bonus == BonusName::BREAK_A_LEG {
+            let reason = match b.edge {
+                None => Some("no edge given".to_string()),
+                Some((v1, v2)) if !problem.figure.edges.iter().any(|&(p1, p2)|
+                        v1 == p1 && v2 == p2 || v1 == p2 && v2 == p1) =>
+                    Some(format!("({}, {}) is not an edge of the figure", v1, v2)),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                violations.push(Violation::InvalidBonus { bonus: b.bonus, reason });
+            }
+        }
+    }
+    violations
 }
 
-pub fn no_glob_check_edge_lens(pose: &Pose, edge_statuses: &[EdgeStatus]) -> bool {
-    let mut cnt = 0;
-    for e in edge_statuses {
-        if e.min_length > e.actual_length 
-            || e.actual_length > e.max_length { cnt += 1; }
+fn edge_status(checker: &mut Checker, edge_idx: usize, pt1: Pt, pt2: Pt) -> EdgeStatus {
+    let (min_length, max_length, original_length_x4) = checker.edge_ranges[edge_idx];
+    EdgeStatus {
+        fits_in_hole: checker.edge_in_hole(pt1, pt2),
+        actual_length: pt1.dist2(pt2),
+        original_length_x4,
+        min_length,
+        max_length,
     }
-    cnt == 0 || cnt == 1 && pose.bonuses.iter().any(|b| b.bonus == BonusName::SUPERFLEX)
 }
 
 pub fn check_pose(problem: &Problem, pose: &Pose) -> CheckPoseResponse {
     let mut checker = Checker::new(problem, &pose.bonuses, pose.vertices.len());
 
     let vertices = &pose.vertices;
+    let mut violations = vec![];
+
+    let expected_vertices = problem.figure.vertices.len()
+        + used(&checker.bonus, &BonusName::BREAK_A_LEG) as usize;
+    if vertices.len() != expected_vertices {
+        violations.push(Violation::WrongVertexCount {
+            expected: expected_vertices, actual: vertices.len() });
+    }
+    violations.extend(bonus_violations(problem, pose, &checker));
+
+    // Larger coordinates would overflow dist2() and friends,
+    // so they are reported instead of looked at further.
+    let in_range: Vec<bool> = vertices.iter()
+        .map(|pt| pt.x.unsigned_abs() <= MAX_COORD as u64 && pt.y.unsigned_abs() <= MAX_COORD as u64)
+        .collect();
+    for (vertex, &pt) in vertices.iter().enumerate() {
+        if !in_range[vertex] {
+            violations.push(Violation::CoordinateOutOfRange { vertex, pt });
+        }
+    }
 
     let mut edge_statuses = vec![];
-    let mut valid = true;
+    // edges with missing or out of range vertices, already reported
+    let mut skip = vec![];
     let mut unlocked = check_unlocked(problem, vertices);
     for i in 0..checker.edges.len() {
-        if checker.edges[i].0 >= vertices.len() || checker.edges[i].1 >= vertices.len() {
+        let (v1, v2) = checker.edges[i];
+        if !in_range.get(v1).copied().unwrap_or(false) || !in_range.get(v2).copied().unwrap_or(false) {
             edge_statuses.push(EdgeStatus { 
                 fits_in_hole: false, actual_length: 0, original_length_x4: 0, 
                 min_length: 0, max_length: 0 });
-            valid = false;
+            skip.push(true);
             continue;
         }
         let pt1 = vertices[checker.edges[i].0];
         let pt2 = vertices[checker.edges[i].1];
-
-        let fits_in_hole = checker.edge_in_hole(pt1, pt2);
-
-        let (min_length, max_length, original_length_x4) = checker.edge_ranges[i];
-
-        let es = EdgeStatus {
-            fits_in_hole,
-            actual_length: pt1.dist2(pt2),
-            original_length_x4,
-            min_length,
-            max_length,
-        };
-        edge_statuses.push(es);
+        edge_statuses.push(edge_status(&mut checker, i, pt1, pt2));
+        skip.push(false);
     }
 
-    valid = valid && pose.bonuses.len() <= 1
-    && (!used(&checker.bonus, &BonusName::BREAK_A_LEG) 
-    || check_valid_break_a_leg(&checker.bonus.clone().unwrap(), problem, pose.vertices.len()))
-    && check_edges_in_hole(problem, pose, &edge_statuses, &checker);
+    violations.extend(hole_violations(pose, &edge_statuses, &skip, &checker));
 
     let bonus_globalist_sum = if used(&checker.bonus, &BonusName::GLOBALIST) {
-        Some(globalist_sum_len(&edge_statuses))
+        Some(globalist_sum_len(&edge_statuses, &skip))
     }
     else { None };
-    valid = valid && if used(&checker.bonus, &BonusName::GLOBALIST) {
-        globalist_check_edge_lens(problem, bonus_globalist_sum.unwrap())
+    if let Some(sum) = bonus_globalist_sum {
+        if !globalist_check_edge_lens(problem, &edge_statuses, &skip) {
+            violations.push(Violation::GlobalistLimitExceeded {
+                sum, limit: problem.figure.edges.len() as f64 * problem.epsilon as f64 });
+        }
+    } else {
+        violations.extend(length_violations(pose, &edge_statuses, &skip));
     }
-    else {
-        no_glob_check_edge_lens(pose, &edge_statuses)
-    };
 
     for _b in &problem.bonuses {
         unlocked.push(false);
     }
 
-    let dislikes = get_dislikes(problem, vertices);
+    let in_range_vertices: Vec<Pt> = vertices.iter().zip(&in_range)
+        .filter(|&(_, &ok)| ok)
+        .map(|(&pt, _)| pt)
+        .collect();
+    let dislikes = get_dislikes(problem, &in_range_vertices);
 
     CheckPoseResponse {
-        edges: problem.figure.edges.clone(),  // TODO: break a leg
+        edges: checker.edges.clone(),
         edge_statuses,
         dislikes,
-        valid,
+        valid: violations.is_empty(),
+        violations,
         unlocked,
         bonus_globalist_sum
     }
 }
 
+crate::entry_point!("check", check_main);
+fn check_main() {
+    let usage = "usage: cargo run check <problem id or file> <pose.json> | --differential [seed] [--problems ...]";
+    match std::env::args().nth(2).as_deref() {
+        Some("--differential") => {
+            let seed = std::env::args().nth(3).filter(|s| !s.starts_with("--"))
+                .map_or(0, |s| s.parse().expect(usage));
+            crate::reference::differential(seed, 200);
+        }
+        Some(problem) => {
+            let problem = match problem.parse() {
+                Ok(problem_id) => crate::util::try_load_problem(problem_id),
+                Err(_) => crate::util::try_load_problem_file(problem),
+            };
+            let problem = problem.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let path = std::env::args().nth(3).expect(usage);
+            let pose: Pose = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+            let r = check_pose(&problem, &pose);
+            for v in &r.violations {
+                println!("{}", v);
+            }
+            println!("{}, {} dislikes", if r.valid { "valid" } else { "not valid" }, r.dislikes);
+        }
+        None => eprintln!("{}", usage),
+    }
+}
+
+// Pose state for local search, updated when a single vertex moves
+// instead of rechecking the whole pose. Plain poses only, no bonuses.
+pub struct IncrementalPose {
+    pub checker: Checker,
+    pub vertices: Vec<Pt>,
+    pub edge_statuses: Vec<EdgeStatus>,
+    // for each hole vertex: squared distance to the nearest pose vertex, its index
+    nearest: Vec<(i64, usize)>,
+    index: NearestIndex,
+    incident: Vec<Vec<usize>>,
+    pub violations: usize,  // edges outside the hole or of wrong length
+    pub dislikes: i64,
+    last_move: Option<Move>,
+}
+
+// Enough to revert a move.
+struct Move {
+    v_idx: usize,
+    old_pt: Pt,
+    edge_statuses: Vec<EdgeStatus>,  // of incident edges, in order
+    nearest: Vec<(usize, (i64, usize))>,  // changed entries only
+    violations: usize,
+    dislikes: i64,
+}
+
+impl IncrementalPose {
+    pub fn new(problem: &Problem, vertices: Vec<Pt>) -> IncrementalPose {
+        assert_eq!(vertices.len(), problem.figure.vertices.len());
+        let mut checker = Checker::new(problem, &[], vertices.len());
+        let mut incident = vec![vec![]; vertices.len()];
+        for (i, &(start, end)) in checker.edges.iter().enumerate() {
+            incident[start].push(i);
+            incident[end].push(i);
+        }
+        let edge_statuses: Vec<EdgeStatus> = checker.edges.clone().into_iter().enumerate()
+            .map(|(i, (start, end))| edge_status(&mut checker, i, vertices[start], vertices[end]))
+            .collect();
+        let index = NearestIndex::new(&vertices);
+        let nearest: Vec<(i64, usize)> = problem.hole.iter()
+            .map(|&h| index.nearest(h).unwrap())
+            .collect();
+        IncrementalPose {
+            checker,
+            violations: edge_statuses.iter().filter(|es| es.is_violated()).count(),
+            dislikes: nearest.iter().map(|&(d, _)| d).sum(),
+            vertices,
+            edge_statuses,
+            nearest,
+            index,
+            incident,
+            last_move: None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.violations == 0
+    }
+
+    /// Indices of the hole vertices this vertex is currently the nearest to
+    /// (one of the nearest, on ties).
+    pub fn nearest_hole_vertices(&self, v_idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.nearest.iter().enumerate()
+            .filter(move |&(_, &(_, v))| v == v_idx)
+            .map(|(i, _)| i)
+    }
+
+    /// O(degree + hole size), plus a nearest vertex query for every hole vertex
+    /// this vertex was the nearest to.
+    pub fn move_vertex(&mut self, v_idx: usize, pt: Pt) {
+        let mut m = Move {
+            v_idx,
+            old_pt: self.vertices[v_idx],
+            edge_statuses: vec![],
+            nearest: vec![],
+            violations: self.violations,
+            dislikes: self.dislikes,
+        };
+        self.vertices[v_idx] = pt;
+        self.index.move_point(v_idx, pt);
+
+        for &e_idx in &self.incident[v_idx] {
+            let (start, end) = self.checker.edges[e_idx];
+            let es = edge_status(&mut self.checker, e_idx, self.vertices[start], self.vertices[end]);
+            if es.is_violated() {
+                self.violations += 1;
+            }
+            let old = std::mem::replace(&mut self.edge_statuses[e_idx], es);
+            if old.is_violated() {
+                self.violations -= 1;
+            }
+            m.edge_statuses.push(old);
+        }
+
+        for (i, &h) in self.checker.problem.hole.iter().enumerate() {
+            let old = self.nearest[i];
+            let d = h.dist2(pt);
+            let new = if d < old.0 {
+                (d, v_idx)
+            } else if old.1 == v_idx && d > old.0 {
+                self.index.nearest(h).unwrap()
+            } else {
+                continue;
+            };
+            self.nearest[i] = new;
+            self.dislikes += new.0 - old.0;
+            m.nearest.push((i, old));
+        }
+
+        self.last_move = Some(m);
+    }
+
+    /// Reverts the last move_vertex().
+    pub fn undo(&mut self) {
+        let m = self.last_move.take().expect("nothing to undo");
+        self.vertices[m.v_idx] = m.old_pt;
+        self.index.move_point(m.v_idx, m.old_pt);
+        for (&e_idx, es) in self.incident[m.v_idx].iter().zip(m.edge_statuses) {
+            self.edge_statuses[e_idx] = es;
+        }
+        for (i, old) in m.nearest {
+            self.nearest[i] = old;
+        }
+        self.violations = m.violations;
+        self.dislikes = m.dislikes;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_incremental_pose() {
+    use rand::prelude::*;
+    let mut rng = StdRng::seed_from_u64(42);
+    for problem_id in [1, 9, 31, 64] {  // 64 is big enough for get_dislikes() to use the grid
+        let p = crate::util::load_problem(problem_id);
+        let mut pose = IncrementalPose::new(&p, p.figure.vertices.clone());
+        let b = pose.checker.hole_index.bbox;
+        for step in 0..300 {
+            let v_idx = rng.gen_range(0..pose.vertices.len());
+            let pt = if rng.gen_bool(0.2) {
+                p.hole[rng.gen_range(0..p.hole.len())]
+            } else {
+                Pt::new(rng.gen_range(b.min_x..=b.max_x), rng.gen_range(b.min_y..=b.max_y))
+            };
+            let before = pose.vertices.clone();
+            pose.move_vertex(v_idx, pt);
+            if step % 3 == 0 {
+                pose.undo();
+                assert_eq!(pose.vertices, before);
+            }
+
+            let r = check_pose(&p, &Pose { vertices: pose.vertices.clone(), bonuses: vec![] });
+            assert_eq!(pose.dislikes, r.dislikes);
+            assert_eq!(pose.dislikes, get_dislikes(&p, &pose.vertices));
+            assert_eq!(pose.violations, r.edge_statuses.iter().filter(|es| es.is_violated()).count());
+            assert_eq!(pose.is_valid(), r.valid);
+            for v in 0..pose.vertices.len() {
+                for h in pose.nearest_hole_vertices(v) {
+                    let d = pose.vertices[v].dist2(p.hole[h]);
+                    assert!(pose.vertices.iter().all(|u| u.dist2(p.hole[h]) >= d));
+                }
+            }
+            for (es, expected) in pose.edge_statuses.iter().zip(&r.edge_statuses) {
+                assert_eq!(es.fits_in_hole, expected.fits_in_hole);
+                assert_eq!(es.actual_length, expected.actual_length);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 #[test]
 fn test_check_pose() {
@@ -293,3 +694,185 @@ fn test_check_pose() {
     };
     dbg!(check_pose(&p, &pose));
 }
+
+#[cfg(test)]
+#[test]
+fn test_violations() {
+    use crate::domain_model::BonusName::*;
+    fn check(p: &Problem, vertices: &[Pt], bonuses: &[(BonusName, Option<(usize, usize)>)]) -> Vec<Violation> {
+        let pose = Pose {
+            vertices: vertices.to_vec(),
+            bonuses: bonuses.iter().map(|&(bonus, edge)| PoseBonus { bonus, problem: 1, edge }).collect(),
+        };
+        let r = check_pose(p, &pose);
+        assert_eq!(r.valid, r.violations.is_empty());
+        r.violations
+    }
+    // a square with a notch coming down to (10, 12)
+    let mut p = crate::util::load_problem(1);
+    p.hole = vec![Pt::new(0, 0), Pt::new(20, 0), Pt::new(20, 20), Pt::new(10, 12), Pt::new(0, 20)];
+    p.figure.vertices = vec![Pt::new(8, 5), Pt::new(12, 5), Pt::new(10, 8), Pt::new(10, 2)];
+    p.figure.edges = vec![(0, 1), (1, 2), (2, 0), (0, 3), (1, 3)];
+    p.epsilon = 0;
+    p.bonuses = vec![];
+    let vs = p.figure.vertices.clone();
+    assert_eq!(check(&p, &vs, &[]), vec![]);
+
+    let mut v = vs.clone();
+    v[0] = Pt::new(7, 5);
+    assert_eq!(check(&p, &v, &[]), vec![
+        Violation::EdgeTooLong { edge: 0, length: 25, max_length: 16 },
+        Violation::EdgeTooLong { edge: 2, length: 18, max_length: 13 },
+        Violation::EdgeTooLong { edge: 3, length: 18, max_length: 13 },
+    ]);
+    v.truncate(3);
+    assert_eq!(check(&p, &v, &[])[0], Violation::WrongVertexCount { expected: 4, actual: 3 });
+
+    // vertex 2 goes into the notch
+    let up: Vec<Pt> = vs.iter().map(|&pt| pt + Pt::new(0, 6)).collect();
+    assert_eq!(check(&p, &up, &[]), vec![Violation::VertexOutsideHole { vertex: 2 }]);
+    assert_eq!(check(&p, &up, &[(WALLHACK, None)]), vec![]);
+
+    assert_eq!(check(&p, &vs, &[(SUPERFLEX, None), (WALLHACK, None)]), vec![
+        Violation::TooManyBonuses { count: 2 },
+    ]);
+    assert_eq!(check(&p, &vs, &[(BREAK_A_LEG, Some((2, 3)))]), vec![
+        Violation::WrongVertexCount { expected: 5, actual: 4 },
+        Violation::InvalidBonus {
+            bonus: BREAK_A_LEG, reason: "(2, 3) is not an edge of the figure".to_string() },
+    ]);
+
+    // edge 0 goes across the notch
+    let v = vec![Pt::new(4, 16), Pt::new(16, 16), Pt::new(10, 8), Pt::new(10, 6)];
+    p.figure.vertices = v.clone();
+    assert_eq!(check(&p, &v, &[]), vec![Violation::EdgeCrossesHole { edge: 0, hole_edge: Some(2) }]);
+    assert_eq!(
+        Violation::EdgeCrossesHole { edge: 0, hole_edge: Some(2) }.to_string(),
+        "edge 0 crosses hole edge 2");
+    assert_eq!(
+        serde_json::to_string(&Violation::EdgeTooLong { edge: 1, length: 5, max_length: 4 }).unwrap(),
+        r#"{"kind":"EdgeTooLong","edge":1,"length":5,"max_length":4}"#);
+}
+
+#[cfg(test)]
+#[test]
+fn test_break_a_leg() {
+    let mut p = crate::util::load_problem(1);
+    p.hole = vec![Pt::new(0, 0), Pt::new(20, 0), Pt::new(20, 20), Pt::new(0, 20)];
+    p.figure.vertices = vec![Pt::new(8, 5), Pt::new(12, 5), Pt::new(10, 8), Pt::new(10, 2)];
+    p.figure.edges = vec![(0, 1), (1, 2), (2, 0), (0, 3), (1, 3)];
+    p.epsilon = 0;
+    p.bonuses = vec![];
+    let leg = PoseBonus { bonus: BonusName::BREAK_A_LEG, problem: 1, edge: Some((1, 0)) };
+    let mut vertices = p.figure.vertices.clone();
+    vertices.push(Pt::new(10, 5));
+    let mut pose = Pose { vertices, bonuses: vec![leg.clone()] };
+
+    let r = check_pose(&p, &pose);
+    assert!(r.valid, "{:?}", r.violations);
+    assert_eq!(r.edges, vec![(1, 2), (2, 0), (0, 3), (1, 3), (1, 4), (0, 4)]);
+    assert_eq!(r.edge_statuses.len(), r.edges.len());
+    for es in &r.edge_statuses[4..] {
+        assert_eq!((es.min_length, es.max_length, es.original_length_x4, es.actual_length), (4, 4, 16, 4));
+    }
+
+    pose.vertices[4] = Pt::new(10, 6);
+    assert_eq!(check_pose(&p, &pose).violations, vec![
+        Violation::EdgeTooLong { edge: 4, length: 5, max_length: 4 },
+        Violation::EdgeTooLong { edge: 5, length: 5, max_length: 4 },
+    ]);
+
+    // Half-edge ranges: |4 * d2 / d - 1| <= eps / EPS_BASE, d the full edge length.
+    for (dx, dy) in [(1, 0), (3, 0), (3, 1), (7, 5), (40, 13)] {
+        for eps in [0, 1, 1000, 150_000, 333_333, 999_999] {
+            p.figure.vertices = vec![Pt::new(0, 0), Pt::new(dx, dy)];
+            p.figure.edges = vec![(0, 1)];
+            p.epsilon = eps;
+            let checker = Checker::new(&p, &[PoseBonus { edge: Some((0, 1)), ..leg.clone() }], 3);
+            let d = dx * dx + dy * dy;
+            for &(min_length, max_length, original_length_x4) in &checker.edge_ranges {
+                assert_eq!(original_length_x4, d);
+                for d2 in 0..=d {
+                    let fits = (4 * d2 * EPS_BASE - d * EPS_BASE).abs() <= eps * d;
+                    assert_eq!(min_length <= d2 && d2 <= max_length, fits, "{} {} {}", d, eps, d2);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_globalist_exact() {
+    let status = |actual_length, original_length_x4| EdgeStatus {
+        fits_in_hole: true, actual_length, original_length_x4, min_length: 0, max_length: 0 };
+    // Six edges off by exactly 1/3 and two exact ones: the sum is 2_000_000,
+    // which floating point used to overshoot.
+    let mut edge_statuses = vec![status(2, 12); 6];
+    edge_statuses.extend(vec![status(1, 4); 2]);
+    let skip = vec![false; 8];
+    let mut p = crate::util::load_problem(1);
+    p.figure.edges = vec![(0, 1); 8];
+    p.epsilon = 250_000;
+    assert!(globalist_check_edge_lens(&p, &edge_statuses, &skip));
+    assert!((globalist_sum_len(&edge_statuses, &skip) - 2e6).abs() < 1e-6);
+    p.epsilon = 249_999;
+    assert!(!globalist_check_edge_lens(&p, &edge_statuses, &skip));
+
+    // many different denominators
+    let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(42);
+    for _ in 0..100 {
+        use rand::Rng;
+        let n = rng.gen_range(1..200);
+        let edge_statuses: Vec<EdgeStatus> = (0..n)
+            .map(|_| status(rng.gen_range(0..1_000_000), 4 * rng.gen_range(1..1_000_000)))
+            .collect();
+        let skip = vec![false; n];
+        p.figure.edges = vec![(0, 1); n];
+        let sum = globalist_sum_len(&edge_statuses, &skip);
+        p.epsilon = (sum / n as f64).floor() as i64;
+        if (p.epsilon * n as i64) as f64 + 1.0 < sum {
+            assert!(!globalist_check_edge_lens(&p, &edge_statuses, &skip));
+        }
+        p.epsilon = (sum / n as f64).ceil() as i64 + 1;
+        assert!(globalist_check_edge_lens(&p, &edge_statuses, &skip));
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_malformed_pose() {
+    use crate::domain_model::BonusName::*;
+    let p = crate::util::load_problem(1);
+    let n = p.figure.vertices.len();
+    let check = |vertices: Vec<Pt>, bonuses: Vec<(BonusName, Option<(usize, usize)>)>| {
+        let pose = Pose {
+            vertices,
+            bonuses: bonuses.into_iter().map(|(bonus, edge)| PoseBonus { bonus, problem: 1, edge }).collect(),
+        };
+        let r = check_pose(&p, &pose);
+        assert!(!r.valid);
+        assert_eq!(r.edge_statuses.len(), r.edges.len());
+        r.violations
+    };
+
+    let mut v = p.figure.vertices.clone();
+    v[0] = Pt::new(i64::MAX, i64::MIN);
+    v[1] = Pt::new(40000, 5);
+    let violations = check(v.clone(), vec![]);
+    assert!(violations.contains(&Violation::CoordinateOutOfRange { vertex: 0, pt: v[0] }));
+    assert!(violations.contains(&Violation::VertexOutsideHole { vertex: 1 }));
+    check(v.clone(), vec![(GLOBALIST, None)]);
+    check(v.clone(), vec![(WALLHACK, None), (SUPERFLEX, None), (GLOBALIST, None)]);
+
+    check(vec![], vec![]);
+    check(vec![], vec![(GLOBALIST, None)]);
+    let mut more = p.figure.vertices.clone();
+    more.push(Pt::new(0, 0));
+    more.push(Pt::new(i64::MIN, 0));
+    check(more.clone(), vec![]);
+    check(more[..n + 1].to_vec(), vec![(BREAK_A_LEG, Some((0, n + 5)))]);
+    check(more[..n + 1].to_vec(), vec![(BREAK_A_LEG, Some((usize::MAX, usize::MAX)))]);
+    check(more[..n + 1].to_vec(), vec![(BREAK_A_LEG, None)]);
+    check(more, vec![(BREAK_A_LEG, Some(p.figure.edges[0]))]);
+}
@@ -0,0 +1,76 @@
+// Replays the poses from cache/server.cache through our checker
+// and compares with what the official server said about them.
+
+use crate::prelude::*;
+use crate::checker::{check_pose, Violation};
+use crate::poses_live::{read_cache, EvaluationResult};
+
+#[derive(serde::Serialize)]
+struct Fixture<'a> {
+    problem_id: i32,
+    pose_id: &'a str,
+    server: EvaluationResult,
+    ours: EvaluationResult,
+    violations: Vec<Violation>,
+    pose: &'a Pose,
+}
+
+crate::entry_point!("checker_conformance", checker_conformance);
+fn checker_conformance() {
+    let cache = read_cache();
+    let dir = project_path("outputs/conformance");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let problem_ids: Vec<i32> = crate::registry::selected_problem_ids().into_iter()
+        .filter(|id| cache.problems.contains_key(id))
+        .collect();
+    let mut agree = 0;
+    let mut disagree = 0;
+    let mut skipped = 0;
+    for problem_id in problem_ids {
+        let problem = load_problem(problem_id);
+        for pi in &cache.problems[&problem_id].poses {
+            let pose = match cache.poses.get(&pi.id) {
+                Some(pose) if pi.er != EvaluationResult::Pending => pose,
+                _ => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let check = check_pose(&problem, pose);
+            let ours = if check.valid {
+                EvaluationResult::Valid { dislikes: check.dislikes }
+            } else {
+                EvaluationResult::Invalid
+            };
+            if ours == pi.er {
+                agree += 1;
+                continue;
+            }
+            disagree += 1;
+            // The server knows which bonuses were actually unlocked, we don't.
+            let bonuses: Vec<String> = pose.bonuses.iter()
+                .map(|b| format!("{:?} from {}", b.bonus, b.problem))
+                .collect();
+            eprintln!("problem {}, pose {}: server {}, ours {}, bonuses [{}]",
+                      problem_id, pi.id, pi.er, ours, bonuses.join(", "));
+            for v in &check.violations {
+                eprintln!("    {}", v);
+            }
+            let fixture = Fixture {
+                problem_id,
+                pose_id: &pi.id,
+                server: pi.er,
+                ours,
+                violations: check.violations,
+                pose,
+            };
+            let path = dir.join(format!("{}_{}.json", problem_id, pi.id));
+            std::fs::write(path, serde_json::to_vec_pretty(&fixture).unwrap()).unwrap();
+        }
+    }
+    eprintln!("{} agree, {} disagree, {} pending or not downloaded", agree, disagree, skipped);
+    if disagree > 0 {
+        eprintln!("disagreeing poses are in outputs/conformance/");
+    }
+}
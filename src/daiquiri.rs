@@ -1,5 +1,5 @@
 use rand::Rng;
-use rand::prelude::ThreadRng;
+use rand::rngs::StdRng;
 use crate::checker::length_range;
 use crate::geom::pt_in_poly;
 use crate::prelude::*;
@@ -9,7 +9,7 @@ const COEFF: i64 = 1;
 
 pub fn daikuiri_shake(r: &ShakeRequest, mojito: bool) -> Vec<Pt> {
     let mut vs = r.vertices.clone();
-    let mut rng = rand::thread_rng();
+    let mut rng = r.rng();
     let in_hole: Vec<bool> = vs.iter().map(|v| pt_in_poly(*v, &r.problem.hole)).collect();
     let ranges: Vec<(i64, i64, i64)> = r.problem.figure.edges.iter().map(
         |e| {
@@ -19,7 +19,15 @@ pub fn daikuiri_shake(r: &ShakeRequest, mojito: bool) -> Vec<Pt> {
                          r.problem.epsilon)
         }).collect();
 
-    for _ in 0..r.param * r.param * COEFF * (r.selected.len() as i64) {
+    for round in 0..r.param * r.param * COEFF * (r.selected.len() as i64) {
+        if r.stop.should_stop() {
+            break;
+        }
+        // violations are the edges of wrong length
+        let wrong = r.problem.figure.edges.iter().zip(&ranges)
+            .filter(|&(&(start, end), &(min_d, max_d, _))| !(min_d..=max_d).contains(&vs[start].dist2(vs[end])))
+            .count();
+        r.progress.report(round as u64, None, None, Some(wrong));
         let mut not_visited: Vec<usize> = vec![];
         for i in 0..r.selected.len() {
             if r.selected[i] { not_visited.push(i); }
@@ -42,14 +50,14 @@ pub fn daikuiri_shake(r: &ShakeRequest, mojito: bool) -> Vec<Pt> {
 //     false
 // }
 
-fn rand_permutation(a: &mut [usize], rng: &mut ThreadRng) {
+fn rand_permutation(a: &mut [usize], rng: &mut StdRng) {
     for i in 0..a.len() {
         a.swap(i, rng.gen_range(0..i+1));
     }
 }
 
 fn shake_one(vs: &mut [Pt], i: usize, r: &ShakeRequest, ranges: &[(i64, i64, i64)], 
-             rng: &mut ThreadRng, keep_in_hole: bool) {
+             rng: &mut StdRng, keep_in_hole: bool) {
     let mut adj_edges = vec![];
     let edges = &r.problem.figure.edges;
     for e in 0..(*edges).len() {
@@ -81,7 +81,7 @@ pub fn update_bonus_graph() -> Result<(), postgres::Error> {
         "DELETE FROM bonuses"
     )?;
     
-    for id in all_problem_ids() {
+    for id in crate::registry::selected_problem_ids() {
         let p = load_problem(id);
         for b in p.bonuses {
             transaction.execute(
@@ -125,7 +125,7 @@ pub fn from_cache() {
         poses_by_vertices.entry(v).or_default().push(str_id.clone());
     }
 
-    for problem_id in all_problem_ids() {
+    for problem_id in crate::registry::selected_problem_ids() {
         let problem = load_problem(problem_id);
         println!("Problem {}", problem_id);
         let pose_ids: &Vec<String> = poses_by_vertices.entry(problem.figure.vertices.len()).or_default();
@@ -7,14 +7,14 @@ use crate::prelude::*;
 
 pub const EPS_BASE: i64 = 1_000_000;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[derive(Debug, Clone)]
 pub struct Figure {
     pub vertices: Vec<Pt>,
     pub edges: Vec<(usize, usize)>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[derive(Debug, Clone)]
 pub struct Problem {
     pub bonuses: Vec<ProblemBonus>,
@@ -33,6 +33,159 @@ impl Problem {
         let t = (best_dislikes as f64 + 1.0) / (dislikes as f64 + 1.0);
         (t * self.weight()).ceil() as i64
     }
+
+    /// Everything the rest of the code takes for granted about a problem,
+    /// plus oddities that are merely suspicious, see ProblemError::is_fatal().
+    pub fn validate(&self) -> Result<(), Vec<ProblemError>> {
+        use crate::geom::{orientation, poly_area_doubled, poly_edges, pt_in_poly, segment_dist2};
+        let mut errors = vec![];
+
+        let hole = &self.hole;
+        let n = hole.len();
+        for i in 0..n {
+            if let Some(j) = (i + 1..n).find(|&j| hole[j] == hole[i]) {
+                errors.push(ProblemError::DuplicateHoleVertex { first: i, second: j });
+            }
+        }
+        if n < 3 {
+            errors.push(ProblemError::HoleTooSmall { vertices: n });
+        } else if errors.is_empty() {
+            let edges: Vec<(Pt, Pt)> = poly_edges(hole).collect();
+            for i in 0..n {
+                for j in i + 1..n {
+                    let bad = if j == i + 1 || i == 0 && j == n - 1 {
+                        // neighbours may only share their common vertex
+                        let (p, q, r) = if j == i + 1 { (hole[i], hole[j], hole[(j + 1) % n]) }
+                                        else { (hole[1], hole[0], hole[n - 1]) };
+                        let (u, v) = (p - q, r - q);
+                        orientation(p, q, r) == 0 && u.x * v.x + u.y * v.y > 0
+                    } else {
+                        segment_dist2(edges[i], edges[j]).is_zero()
+                    };
+                    if bad {
+                        errors.push(ProblemError::HoleSelfIntersecting { edge1: i, edge2: j });
+                    }
+                }
+            }
+            if poly_area_doubled(hole) == 0 {
+                errors.push(ProblemError::HoleZeroArea);
+            }
+        }
+
+        let vs = &self.figure.vertices;
+        if vs.is_empty() {
+            errors.push(ProblemError::EmptyFigure);
+        }
+        for i in 0..vs.len() {
+            if let Some(j) = (i + 1..vs.len()).find(|&j| vs[j] == vs[i]) {
+                errors.push(ProblemError::DuplicateFigureVertex { first: i, second: j });
+            }
+        }
+        let mut adj = vec![vec![]; vs.len()];
+        let mut seen = std::collections::HashMap::new();
+        for (edge, &(a, b)) in self.figure.edges.iter().enumerate() {
+            if a >= vs.len() || b >= vs.len() {
+                errors.push(ProblemError::EdgeIndexOutOfRange { edge, vertex: a.max(b) });
+                continue;
+            }
+            if vs[a] == vs[b] {
+                errors.push(ProblemError::ZeroLengthEdge { edge });
+            }
+            if let Some(&first) = seen.get(&(a.min(b), a.max(b))) {
+                errors.push(ProblemError::DuplicateEdge { first, second: edge });
+            } else {
+                seen.insert((a.min(b), a.max(b)), edge);
+            }
+            adj[a].push(b);
+            adj[b].push(a);
+        }
+        if !vs.is_empty() {
+            let mut visited = vec![false; vs.len()];
+            visited[0] = true;
+            let mut stack = vec![0];
+            while let Some(v) = stack.pop() {
+                for &u in &adj[v] {
+                    if !visited[u] {
+                        visited[u] = true;
+                        stack.push(u);
+                    }
+                }
+            }
+            if let Some(vertex) = visited.iter().position(|&v| !v) {
+                errors.push(ProblemError::DisconnectedFigure { unreachable: vertex });
+            }
+        }
+
+        if n >= 3 {
+            for (i, b) in self.bonuses.iter().enumerate() {
+                if !pt_in_poly(b.position, hole) {
+                    errors.push(ProblemError::BonusOutsideHole { bonus: i });
+                }
+            }
+        }
+
+        if self.epsilon < 0 {
+            errors.push(ProblemError::NegativeEpsilon);
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+// What Problem::validate() finds. Indices are into hole, figure.vertices,
+// figure.edges or bonuses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProblemError {
+    HoleTooSmall { vertices: usize },
+    DuplicateHoleVertex { first: usize, second: usize },
+    HoleSelfIntersecting { edge1: usize, edge2: usize },
+    HoleZeroArea,  // so neither clockwise nor counterclockwise
+    EmptyFigure,
+    DuplicateFigureVertex { first: usize, second: usize },
+    EdgeIndexOutOfRange { edge: usize, vertex: usize },
+    ZeroLengthEdge { edge: usize },
+    DuplicateEdge { first: usize, second: usize },
+    DisconnectedFigure { unreachable: usize },
+    BonusOutsideHole { bonus: usize },
+    NegativeEpsilon,
+}
+
+impl ProblemError {
+    // Some official problems have these, and they do no harm.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, ProblemError::DuplicateFigureVertex { .. } | ProblemError::DuplicateEdge { .. })
+    }
+}
+
+impl std::fmt::Display for ProblemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProblemError::HoleTooSmall { vertices } =>
+                write!(f, "hole has {} vertices", vertices),
+            ProblemError::DuplicateHoleVertex { first, second } =>
+                write!(f, "hole vertices {} and {} coincide", first, second),
+            ProblemError::HoleSelfIntersecting { edge1, edge2 } =>
+                write!(f, "hole edges {} and {} intersect", edge1, edge2),
+            ProblemError::HoleZeroArea =>
+                write!(f, "hole has zero area"),
+            ProblemError::EmptyFigure =>
+                write!(f, "figure has no vertices"),
+            ProblemError::DuplicateFigureVertex { first, second } =>
+                write!(f, "figure vertices {} and {} coincide", first, second),
+            ProblemError::EdgeIndexOutOfRange { edge, vertex } =>
+                write!(f, "figure edge {} refers to vertex {} that doesn't exist", edge, vertex),
+            ProblemError::ZeroLengthEdge { edge } =>
+                write!(f, "figure edge {} has zero length", edge),
+            ProblemError::DuplicateEdge { first, second } =>
+                write!(f, "figure edges {} and {} are the same", first, second),
+            ProblemError::DisconnectedFigure { unreachable } =>
+                write!(f, "figure vertex {} is not connected to vertex 0", unreachable),
+            ProblemError::BonusOutsideHole { bonus } =>
+                write!(f, "bonus {} is outside the hole", bonus),
+            ProblemError::NegativeEpsilon =>
+                write!(f, "epsilon is negative"),
+        }
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -110,3 +263,44 @@ fn test_problem_parsing() {
     let problem: Problem = serde_json::from_slice(&data).unwrap();
     dbg!(problem);
 }
+
+#[cfg(test)]
+#[test]
+fn test_validate() {
+    for problem_id in all_problem_ids() {
+        let p = load_problem(problem_id);
+        if let Err(errors) = p.validate() {
+            assert!(errors.iter().all(|e| !e.is_fatal()), "problem {}: {:?}", problem_id, errors);
+        }
+    }
+
+    let mut p = load_problem(1);
+    p.hole = vec![Pt::new(0, 0), Pt::new(10, 0), Pt::new(10, 10), Pt::new(0, 10)];
+    p.figure.vertices = vec![Pt::new(1, 1), Pt::new(2, 1), Pt::new(3, 3), Pt::new(5, 5)];
+    p.figure.edges = vec![(0, 1), (1, 2), (2, 0)];
+    p.bonuses.truncate(1);
+    p.bonuses[0].position = Pt::new(11, 5);
+    let mut q = p.clone();
+    q.figure.edges.push((3, 3));
+    q.figure.edges.push((1, 0));
+    q.figure.edges.push((2, 4));
+    assert_eq!(p.validate(), Err(vec![
+        ProblemError::DisconnectedFigure { unreachable: 3 },
+        ProblemError::BonusOutsideHole { bonus: 0 },
+    ]));
+    assert_eq!(q.validate(), Err(vec![
+        ProblemError::ZeroLengthEdge { edge: 3 },
+        ProblemError::DuplicateEdge { first: 0, second: 4 },
+        ProblemError::EdgeIndexOutOfRange { edge: 5, vertex: 4 },
+        ProblemError::DisconnectedFigure { unreachable: 3 },
+        ProblemError::BonusOutsideHole { bonus: 0 },
+    ]));
+
+    // bow tie, then a spike going back along its own edge
+    p.hole = vec![Pt::new(0, 0), Pt::new(10, 10), Pt::new(10, 0), Pt::new(0, 10)];
+    assert!(p.validate().unwrap_err().contains(&ProblemError::HoleSelfIntersecting { edge1: 0, edge2: 2 }));
+    p.hole = vec![Pt::new(0, 0), Pt::new(10, 0), Pt::new(5, 0), Pt::new(5, 5)];
+    assert!(p.validate().unwrap_err().contains(&ProblemError::HoleSelfIntersecting { edge1: 0, edge2: 1 }));
+    p.hole = vec![Pt::new(0, 0), Pt::new(10, 0), Pt::new(20, 0)];
+    assert!(p.validate().unwrap_err().contains(&ProblemError::HoleZeroArea));
+}
@@ -0,0 +1,205 @@
+// Random problems with a planted solution, to measure the solvers against.
+//
+// The planted pose goes first: every hole vertex is a pose vertex
+// (so it scores 0 dislikes, which is optimal), the rest are lattice points
+// inside the hole. Edges are the hole boundary plus segments from each
+// inner vertex to its nearest visible predecessors. The figure is the planted
+// pose moved next to the hole by a lattice symmetry and a translation,
+// so all edge lengths are exact and the pose is valid for any epsilon.
+
+use rand::prelude::*;
+use crate::prelude::*;
+use crate::checker::check_pose;
+use crate::domain_model::{BonusName, ProblemBonus};
+use crate::geom::{pt_in_poly, segment_in_poly, BBox, Transform};
+
+#[derive(Clone, Debug)]
+pub struct GenParams {
+    pub vertices: usize,       // in the figure, at least hole_vertices
+    pub hole_vertices: usize,
+    pub spikiness: f64,        // 0 for a convex-ish hole, up to 1 for a star
+    pub size: i64,             // hole radius
+    pub epsilon: i64,
+    pub bonuses: Vec<BonusName>,
+    pub bonus_target: i32,
+}
+
+impl Default for GenParams {
+    fn default() -> Self {
+        GenParams {
+            vertices: 20,
+            hole_vertices: 8,
+            spikiness: 0.5,
+            size: 50,
+            epsilon: 10000,
+            bonuses: vec![],
+            bonus_target: 0,
+        }
+    }
+}
+
+// None if rounding keeps breaking it, as it does for many vertices in a small hole.
+fn random_hole(params: &GenParams, rng: &mut StdRng) -> Option<Vec<Pt>> {
+    let r = params.size as f64;
+    for _ in 0..100 {
+        let mut angles: Vec<f64> = (0..params.hole_vertices)
+            .map(|_| rng.gen_range(0.0..std::f64::consts::TAU))
+            .collect();
+        angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let hole: Vec<Pt> = angles.iter().map(|a| {
+            let radius = r * (1.0 - params.spikiness * rng.gen_range(0.0..1.0));
+            Pt::new(params.size + (radius * a.cos()).round() as i64,
+                    params.size + (radius * a.sin()).round() as i64)
+        }).collect();
+        // rounding may have broken it
+        let p = Problem {
+            bonuses: vec![],
+            hole: hole.clone(),
+            figure: Figure { vertices: vec![hole[0]], edges: vec![] },
+            epsilon: 0,
+        };
+        if p.validate().is_ok() {
+            return Some(hole);
+        }
+    }
+    None
+}
+
+/// A problem and its planted zero-dislike pose, or None if the hole
+/// turned out too small for that many distinct vertices.
+fn try_generate(params: &GenParams, rng: &mut StdRng) -> Option<(Problem, Pose)> {
+    let hole = random_hole(params, rng)?;
+    let k = hole.len();
+    let mut planted = hole.clone();
+    let mut edges: Vec<(usize, usize)> = (0..k).map(|i| (i, (i + 1) % k)).collect();
+    let b = BBox::from_pts(&hole);
+    for v in k..params.vertices {
+        let mut placed = false;
+        for _ in 0..1000 {
+            let pt = Pt::new(rng.gen_range(b.min_x..=b.max_x), rng.gen_range(b.min_y..=b.max_y));
+            if !pt_in_poly(pt, &hole) || planted.contains(&pt) {
+                continue;
+            }
+            let mut near: Vec<usize> = (0..v).collect();
+            near.sort_by_key(|&u| planted[u].dist2(pt));
+            let visible: Vec<usize> = near.into_iter()
+                .filter(|&u| segment_in_poly((planted[u], pt), &hole))
+                .take(rng.gen_range(1..=3))
+                .collect();
+            if visible.is_empty() {
+                continue;
+            }
+            planted.push(pt);
+            edges.extend(visible.into_iter().map(|u| (u, v)));
+            placed = true;
+            break;
+        }
+        if !placed {
+            return None;
+        }
+    }
+
+    let t = Transform::lattice_symmetries()[rng.gen_range(0..8)];
+    let mut vertices: Vec<Pt> = planted.iter().map(|&p| t.apply(p).unwrap()).collect();
+    let fb = BBox::from_pts(&vertices);
+    let shift = Pt::new(b.max_x + params.size / 5 - fb.min_x, b.min_y - fb.min_y);
+    for p in &mut vertices {
+        *p = *p + shift;
+    }
+
+    let bonuses = params.bonuses.iter().map(|&bonus| ProblemBonus {
+        bonus,
+        problem: params.bonus_target,
+        position: planted[rng.gen_range(0..planted.len())],
+    }).collect();
+    let problem = Problem {
+        bonuses,
+        hole,
+        figure: Figure { vertices, edges },
+        epsilon: params.epsilon,
+    };
+    Some((problem, Pose { vertices: planted, bonuses: vec![] }))
+}
+
+pub fn generate(params: &GenParams, rng: &mut StdRng) -> (Problem, Pose) {
+    assert!(params.hole_vertices >= 3 && params.vertices >= params.hole_vertices);
+    assert!((0.0..1.0).contains(&params.spikiness));
+    for _ in 0..100 {
+        if let Some((problem, pose)) = try_generate(params, rng) {
+            let check = check_pose(&problem, &pose);
+            assert!(check.valid && check.dislikes == 0, "{:?}", check.violations);
+            return (problem, pose);
+        }
+    }
+    panic!("can't fit {} vertices and a hole of {} in size {}", params.vertices, params.hole_vertices, params.size);
+}
+
+crate::entry_point!("gen_problem", gen_problem);
+fn gen_problem() {
+    use crate::util::flag_value;
+    // usage: cargo run gen_problem [--count 10] [--first-id 1001] [--seed 42]
+    //   [--vertices 20] [--hole 8] [--spikiness 0.5] [--size 50]
+    //   [--epsilon 10000] [--bonuses GLOBALIST,WALLHACK] [--out outputs/generated]
+    fn flag<T: std::str::FromStr>(name: &str, default: T) -> T {
+        match flag_value(name) {
+            Some(s) => s.parse().unwrap_or_else(|_| panic!("--{} {:?}", name, s)),
+            None => default,
+        }
+    }
+    let count: i32 = flag("count", 10);
+    let first_id: i32 = flag("first-id", 1001);
+    let mut rng = StdRng::seed_from_u64(flag("seed", 42));
+    let d = GenParams::default();
+    let mut params = GenParams {
+        vertices: flag("vertices", d.vertices),
+        hole_vertices: flag("hole", d.hole_vertices),
+        spikiness: flag("spikiness", d.spikiness),
+        size: flag("size", d.size),
+        epsilon: flag("epsilon", d.epsilon),
+        bonuses: flag_value("bonuses").unwrap_or_default()
+            .split(',').filter(|s| !s.is_empty())
+            .map(|s| serde_json::from_value(serde_json::Value::String(s.to_string())).unwrap())
+            .collect(),
+        bonus_target: 0,
+    };
+    let out = flag_value("out").unwrap_or_else(|| "outputs/generated".to_string());
+    let dir = project_path(&out);
+    std::fs::create_dir_all(&dir).unwrap();
+    for id in first_id..first_id + count {
+        // bonuses chain through the batch
+        params.bonus_target = first_id + (id - first_id + 1) % count;
+        let (problem, pose) = generate(&params, &mut rng);
+        std::fs::write(dir.join(format!("{}.problem", id)), serde_json::to_vec(&problem).unwrap()).unwrap();
+        std::fs::write(dir.join(format!("{}.planted.json", id)), serde_json::to_vec(&pose).unwrap()).unwrap();
+        println!("{}: {} vertices, {} edges, hole {}", id,
+                 problem.figure.vertices.len(), problem.figure.edges.len(), problem.hole.len());
+    }
+    println!("see {}/, use EXTRA_PROBLEM_DIRS={} to load them", out, dir.display());
+}
+
+#[cfg(test)]
+#[test]
+fn test_generate() {
+    let mut rng = StdRng::seed_from_u64(1);
+    for &(vertices, hole_vertices, spikiness) in &[(3, 3, 0.0), (20, 8, 0.5), (60, 25, 0.9)] {
+        let params = GenParams {
+            vertices,
+            hole_vertices,
+            spikiness,
+            bonuses: vec![BonusName::GLOBALIST],
+            ..GenParams::default()
+        };
+        let (problem, pose) = generate(&params, &mut rng);
+        assert_eq!(problem.figure.vertices.len(), vertices);
+        assert_eq!(problem.hole.len(), hole_vertices);
+        problem.validate().unwrap();
+        assert!(pose.vertices.contains(&problem.bonuses[0].position));
+        // survives the round trip through a .problem file
+        let problem: Problem = serde_json::from_slice(&serde_json::to_vec(&problem).unwrap()).unwrap();
+        let check = check_pose(&problem, &pose);
+        assert!(check.valid && check.dislikes == 0);
+    }
+
+    let params = GenParams { vertices: 200, hole_vertices: 200, size: 10, ..GenParams::default() };
+    assert!(try_generate(&params, &mut rng).is_none());
+}
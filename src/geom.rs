@@ -11,6 +11,9 @@ pub struct Pt {
     pub y: i64,  // y axis points down
 }
 
+// No hole comes close. Larger coordinates would overflow dist2() and friends.
+pub const MAX_COORD: i64 = 1 << 24;
+
 impl Pt {
     pub fn new(x: i64, y: i64) -> Self {
         Pt { x, y }
@@ -112,18 +115,19 @@ pub fn segment_intersection((pt1, pt2): (Pt, Pt), (pt3, pt4): (Pt, Pt)) -> Inter
         y_numer = -y_numer;
     }
 
+    // The crossing point has to be on both segments before it can be an endpoint:
+    // lines may meet at an end of one segment and miss the other.
+    if !(bb_x1 * denom <= x_numer && x_numer <= bb_x2 * denom
+    && bb_y1 * denom <= y_numer && y_numer <= bb_y2 * denom) {
+        return Intersection::No;
+    }
+
     for &pt in &[pt1, pt2, pt3, pt4] {
         if pt.x * denom == x_numer && pt.y * denom == y_numer {
             return Intersection::Endpoint(pt);
         }
     }
-
-    if bb_x1 * denom <= x_numer && x_numer <= bb_x2 * denom
-    && bb_y1 * denom <= y_numer && y_numer <= bb_y2 * denom {
-        Intersection::Internal
-    } else {
-        Intersection::No
-    }
+    Intersection::Internal
 }
 
 #[cfg(test)]
@@ -156,6 +160,12 @@ fn test_intersection() {
         (Pt::new(0, 0), Pt::new(10, 20)),
         (Pt::new(5, 10), Pt::new(30, 60)),
         Intersection::No);
+
+    // the lines meet at an end of the second segment, outside the first one
+    check_intersection(
+        (Pt::new(35, 95), Pt::new(5, 95)),
+        (Pt::new(35, 5), Pt::new(95, 95)),
+        Intersection::No);
 }
 
 pub fn poly_edges(poly: &[Pt]) -> impl Iterator<Item=(Pt, Pt)> + '_ {
@@ -285,13 +295,68 @@ pub fn pt_in_segment(pt: Pt, seg: (Pt, Pt)) -> bool {
     (seg.0 - pt).cross(seg.0 - seg.1) == 0
 }
 
+/// Sign of the turn a -> b -> c: positive if c is to the left of ab
+/// (in the math orientation), zero if collinear.
+pub fn orientation(a: Pt, b: Pt, c: Pt) -> i64 {
+    (b - a).cross(c - a).signum()
+}
+
+// Is pt/scale inside the polygon with the given edges (boundary included)?
+// Same ray casting as in pt_in_poly, but the polygon is scaled instead of the point
+// so that everything stays in integers.
+// Only edges whose y-range contains pt.y matter, so a subset of polygon edges
+// containing all of those gives the same answer.
+pub fn pt_in_scaled_edges(pt: Pt, scale: i64, edges: impl IntoIterator<Item=(Pt, Pt)>) -> bool {
+    let mut odd = false;
+    for (pt1, pt2) in edges {
+        let pt1 = Pt::new(pt1.x * scale, pt1.y * scale);
+        let pt2 = Pt::new(pt2.x * scale, pt2.y * scale);
+        if pt == pt1 {
+            return true;
+        }
+        if pt1.y == pt2.y {
+            if pt.y == pt1.y &&
+               pt1.x.min(pt2.x) <= pt.x && pt.x <= pt1.x.max(pt2.x) {
+                return true;
+            }
+            continue;
+        }
+        if pt1.y.min(pt2.y) <= pt.y && pt.y < pt1.y.max(pt2.y) {
+            match orientation(pt1, pt2, pt) * (pt2.y - pt1.y).signum() {
+                0 => return true,
+                s if s > 0 => odd = !odd,
+                _ => {}
+            }
+        }
+    }
+    odd
+}
+
 // including boundary
 pub fn segment_in_poly(seg: (Pt, Pt), poly: &[Pt]) -> bool {
-    // TODO: This check is incorrect in some cases.
-    // I think I know how to do the check correctly,
-    // but it's hard, this is a placeholder for now.
+    segment_in_edges(seg, poly_edges(poly), |pt, scale| {
+        pt_in_scaled_edges(pt, scale, poly_edges(poly))
+    })
+}
 
-    if !pt_in_poly(seg.0, poly) || !pt_in_poly(seg.1, poly) {
+// The actual implementation of segment_in_poly.
+// `edges` must include every polygon edge that touches the segment,
+// `pt_in(pt, scale)` tells if pt/scale is inside the polygon (boundary included).
+pub fn segment_in_edges(
+    seg: (Pt, Pt),
+    edges: impl IntoIterator<Item=(Pt, Pt)>,
+    mut pt_in: impl FnMut(Pt, i64) -> bool,
+) -> bool {
+    // Exact check, only integer orientation predicates are used.
+    //
+    // The segment is split at every point where it touches the polygon
+    // boundary without crossing it: its own endpoints and the polygon
+    // vertices lying on it. A proper crossing with an edge means we are
+    // outside right away. Otherwise each piece between two consecutive
+    // split points is either entirely inside, entirely outside,
+    // or runs along the boundary, so checking its midpoint is enough.
+
+    if !pt_in(seg.0, 1) || !pt_in(seg.1, 1) {
         return false;
     }
 
@@ -299,77 +364,27 @@ pub fn segment_in_poly(seg: (Pt, Pt), poly: &[Pt]) -> bool {
         return true;
     }
 
-    let sign = poly_area_doubled(poly).signum();
-
-    for edge in poly_edges(poly) {
-        match segment_intersection(seg, edge) {
-            Intersection::Internal => return false,
-            Intersection::No => {}
-            Intersection::Endpoint(pt) => {
-                if pt != edge.0 && pt != edge.1 {
-                    assert!(pt == seg.0 || pt == seg.1);
-                    let other = seg.0 + seg.1 - pt;
-                    let d = other - pt;
-                    let d1 = edge.0 - pt;
-                    let d2 = edge.1 - pt;
-                    if sign * qqqqqq(d1, d, d2) < 0 {
-                        return false;
-                    }
-                }
-            }
+    let (a, b) = seg;
+    let mut splits = vec![a, b];
+    for (p, q) in edges {
+        let o1 = orientation(a, b, p);
+        let o2 = orientation(a, b, q);
+        if o1 * o2 < 0 && orientation(p, q, a) * orientation(p, q, b) < 0 {
+            return false;
         }
-    }
-
-    for &(a, b) in &[seg, (seg.1, seg.0)] {
-        if let Some(i) = poly.iter().position(|&p| p == a) {
-            let i1 = if i == 0 { poly.len() - 1 } else { i - 1 };
-            let i2 = if i == poly.len() - 1 { 0 } else { i + 1 };
-            let d1 = poly[i1] - poly[i];
-            let d2 = poly[i2] - poly[i];
-            let d = b - a;
-            if sign * qqqqqq(d1, d, d2) < 0 {
-                return false;
-            }
+        if o1 == 0 && pt_in_segment(p, seg) {
+            splits.push(p);
         }
-    }
-
-    for i in 0..poly.len() {
-        if !pt_in_segment(poly[i], seg) {
-            continue;
-        }
-        let i1 = if i == 0 { poly.len() - 1 } else { i - 1 };
-        let i2 = if i == poly.len() - 1 { 0 } else { i + 1 };
-        for &other in &[seg.0, seg.1] {
-            if other == poly[i] {
-                continue;
-            }
-            let d1 = poly[i1] - poly[i];
-            let d2 = poly[i2] - poly[i];
-            let d = other - poly[i];
-            if sign * qqqqqq(d1, d, d2) < 0 {
-                return false;
-            }
+        if o2 == 0 && pt_in_segment(q, seg) {
+            splits.push(q);
         }
     }
 
-    true
-}
+    let d = b - a;
+    splits.sort_by_key(|&p| (p.x - a.x) * d.x + (p.y - a.y) * d.y);
+    splits.dedup();
 
-fn qqqqqq(v1: Pt, v2: Pt, v3: Pt) -> i64 {
-    let a = [v1.angle(), v2.angle(), v3.angle()];
-    let mut res = 1;
-    for i in 0..3 {
-        for j in 0..i {
-            #[allow(clippy::float_cmp)]
-            if a[i] == a[j] {
-                return 0;
-            }
-            if a[i] < a[j] {
-                res = -res;
-            }
-        }
-    }
-    res
+    splits.windows(2).all(|w| pt_in(w[0] + w[1], 2))
 }
 
 #[cfg(test)]
@@ -417,6 +432,424 @@ fn test_segment_in_poly_bug() {
     check_segment_in_poly((Pt::new(-2, 3), Pt::new(2, 3)), &poly, false);
 }
 
+#[cfg(test)]
+#[test]
+fn test_segment_in_poly_degenerate() {
+    // L-shape with a reflex vertex at (5, 5)
+    let poly = vec![
+        Pt::new(0, 0),
+        Pt::new(10, 0),
+        Pt::new(10, 5),
+        Pt::new(5, 5),
+        Pt::new(5, 10),
+        Pt::new(0, 10),
+    ];
+    // along hole edges
+    check_segment_in_poly((Pt::new(0, 0), Pt::new(10, 0)), &poly, true);
+    check_segment_in_poly((Pt::new(2, 0), Pt::new(8, 0)), &poly, true);
+    check_segment_in_poly((Pt::new(5, 2), Pt::new(5, 10)), &poly, true);
+    check_segment_in_poly((Pt::new(0, 5), Pt::new(10, 5)), &poly, true);
+    check_segment_in_poly((Pt::new(0, 10), Pt::new(0, 0)), &poly, true);
+
+    // through the reflex vertex
+    check_segment_in_poly((Pt::new(0, 10), Pt::new(10, 0)), &poly, true);
+    check_segment_in_poly((Pt::new(0, 0), Pt::new(10, 10)), &poly, false);
+    check_segment_in_poly((Pt::new(0, 0), Pt::new(7, 7)), &poly, false);
+    check_segment_in_poly((Pt::new(3, 3), Pt::new(5, 5)), &poly, true);
+
+    // between two vertices across the notch
+    check_segment_in_poly((Pt::new(5, 10), Pt::new(10, 5)), &poly, false);
+    check_segment_in_poly((Pt::new(4, 10), Pt::new(10, 4)), &poly, false);
+    check_segment_in_poly((Pt::new(4, 6), Pt::new(6, 4)), &poly, true);
+
+    // Comb: several vertices on the line y = 5, notches reaching down to y = 3.
+    let poly = vec![
+        Pt::new(0, 0),
+        Pt::new(10, 0),
+        Pt::new(10, 5),
+        Pt::new(8, 3),
+        Pt::new(6, 5),
+        Pt::new(4, 3),
+        Pt::new(2, 5),
+        Pt::new(0, 5),
+    ];
+    check_segment_in_poly((Pt::new(0, 5), Pt::new(2, 5)), &poly, true);
+    check_segment_in_poly((Pt::new(0, 5), Pt::new(10, 5)), &poly, false);
+    check_segment_in_poly((Pt::new(2, 5), Pt::new(6, 5)), &poly, false);
+    check_segment_in_poly((Pt::new(0, 3), Pt::new(10, 3)), &poly, true);
+    check_segment_in_poly((Pt::new(0, 4), Pt::new(10, 4)), &poly, false);
+    check_segment_in_poly((Pt::new(4, 3), Pt::new(8, 3)), &poly, true);
+    check_segment_in_poly((Pt::new(2, 5), Pt::new(4, 3)), &poly, true);
+    check_segment_in_poly((Pt::new(2, 5), Pt::new(8, 3)), &poly, false);
+
+    // degenerate segment
+    check_segment_in_poly((Pt::new(6, 5), Pt::new(6, 5)), &poly, true);
+    check_segment_in_poly((Pt::new(4, 5), Pt::new(4, 5)), &poly, false);
+}
+
+/// Exact nonnegative fraction num / den, used for squared distances
+/// that are not integers.
+#[derive(Clone, Copy, Debug)]
+pub struct Ratio {
+    pub num: i64,
+    pub den: i64,  // positive
+}
+
+impl Ratio {
+    pub fn new(num: i64, den: i64) -> Ratio {
+        assert!(den > 0);
+        Ratio { num, den }
+    }
+
+    pub fn zero() -> Ratio {
+        Ratio { num: 0, den: 1 }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.num == 0
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl From<i64> for Ratio {
+    fn from(x: i64) -> Ratio {
+        Ratio { num: x, den: 1 }
+    }
+}
+
+impl PartialEq for Ratio {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Ratio {}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.num as i128 * other.den as i128).cmp(&(other.num as i128 * self.den as i128))
+    }
+}
+
+/// Squared distance from the point to the closest point of the segment.
+pub fn pt_segment_dist2(pt: Pt, seg: (Pt, Pt)) -> Ratio {
+    let (a, b) = seg;
+    let d = b - a;
+    let dot = |u: Pt, v: Pt| u.x * v.x + u.y * v.y;
+    if dot(pt - a, d) <= 0 {
+        return pt.dist2(a).into();
+    }
+    if dot(pt - b, d) >= 0 {
+        return pt.dist2(b).into();
+    }
+    let c = d.cross(pt - a);
+    Ratio::new(c * c, d.len2())
+}
+
+/// Squared distance between two closed segments (zero if they touch).
+pub fn segment_dist2(seg1: (Pt, Pt), seg2: (Pt, Pt)) -> Ratio {
+    let touch = if seg1.0 == seg1.1 || seg2.0 == seg2.1 {
+        false
+    } else {
+        segment_intersection(seg1, seg2) != Intersection::No
+    };
+    if touch {
+        return Ratio::zero();
+    }
+    // Collinear overlaps and touching endpoints are caught here as well.
+    [
+        pt_segment_dist2(seg1.0, seg2),
+        pt_segment_dist2(seg1.1, seg2),
+        pt_segment_dist2(seg2.0, seg1),
+        pt_segment_dist2(seg2.1, seg1),
+    ].iter().copied().min().unwrap()
+}
+
+/// Squared distance from the point to the polygon boundary.
+pub fn pt_boundary_dist2(pt: Pt, poly: &[Pt]) -> Ratio {
+    poly_edges(poly).map(|e| pt_segment_dist2(pt, e)).min().unwrap()
+}
+
+/// How far the point lies outside the polygon:
+/// zero if it's inside (boundary included),
+/// otherwise the squared distance to the polygon.
+pub fn pt_outside_dist2(pt: Pt, poly: &[Pt]) -> Ratio {
+    if pt_in_poly(pt, poly) {
+        Ratio::zero()
+    } else {
+        pt_boundary_dist2(pt, poly)
+    }
+}
+
+/// How far the segment lies outside the polygon.
+///
+/// If an endpoint is outside, it's the larger of the endpoint distances
+/// (bringing the endpoints back is what's needed first).
+/// Otherwise the segment cuts through parts of the outside, and for every
+/// polygon edge it crosses we take the squared distance from the segment to
+/// the nearer end of that edge, which is how far the segment has to be moved
+/// to clear it.
+///
+/// Zero for segments inside the polygon. A segment leaving the polygon only
+/// through polygon vertices also gets zero, so combine this with
+/// segment_in_poly when validity matters.
+pub fn segment_outside_dist2(seg: (Pt, Pt), poly: &[Pt]) -> Ratio {
+    let d1 = pt_outside_dist2(seg.0, poly);
+    let d2 = pt_outside_dist2(seg.1, poly);
+    if !d1.is_zero() || !d2.is_zero() {
+        return d1.max(d2);
+    }
+    let mut result = Ratio::zero();
+    if seg.0 == seg.1 {
+        return result;
+    }
+    for (p, q) in poly_edges(poly) {
+        if orientation(seg.0, seg.1, p) * orientation(seg.0, seg.1, q) < 0 &&
+           orientation(p, q, seg.0) * orientation(p, q, seg.1) < 0 {
+            result = result.max(pt_segment_dist2(p, seg).min(pt_segment_dist2(q, seg)));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+#[test]
+fn test_distances() {
+    let seg = (Pt::new(0, 0), Pt::new(4, 2));
+    assert_eq!(pt_segment_dist2(Pt::new(-1, 0), seg), 1.into());
+    assert_eq!(pt_segment_dist2(Pt::new(5, 3), seg), 2.into());
+    assert_eq!(pt_segment_dist2(Pt::new(2, 1), seg), 0.into());
+    // distance to the line is |cross| / |d| = 10 / sqrt(20)
+    assert_eq!(pt_segment_dist2(Pt::new(1, 3), seg), Ratio::new(100, 20));
+
+    assert_eq!(segment_dist2(seg, (Pt::new(0, 1), Pt::new(1, 0))), Ratio::zero());
+    assert_eq!(segment_dist2(seg, (Pt::new(8, 4), Pt::new(6, 3))), 5.into());
+    assert_eq!(segment_dist2(seg, (Pt::new(0, 3), Pt::new(4, 5))), Ratio::new(36, 5));
+    // the lines meet at (95, 95), an end of the second segment but past the first one
+    assert_eq!(segment_dist2((Pt::new(35, 95), Pt::new(5, 95)), (Pt::new(35, 5), Pt::new(95, 95))),
+               Ratio::new(5400 * 5400, 60 * 60 + 90 * 90));
+
+    // L-shape with a reflex vertex at (5, 5)
+    let poly = vec![
+        Pt::new(0, 0),
+        Pt::new(10, 0),
+        Pt::new(10, 5),
+        Pt::new(5, 5),
+        Pt::new(5, 10),
+        Pt::new(0, 10),
+    ];
+    assert_eq!(pt_outside_dist2(Pt::new(3, 3), &poly), Ratio::zero());
+    assert_eq!(pt_outside_dist2(Pt::new(5, 7), &poly), Ratio::zero());
+    assert_eq!(pt_outside_dist2(Pt::new(7, 8), &poly), 4.into());
+    assert_eq!(pt_outside_dist2(Pt::new(12, 12), &poly), 53.into());
+    assert_eq!(pt_boundary_dist2(Pt::new(3, 3), &poly), 8.into());
+
+    assert_eq!(segment_outside_dist2((Pt::new(0, 0), Pt::new(10, 5)), &poly), Ratio::zero());
+    assert_eq!(segment_outside_dist2((Pt::new(3, 3), Pt::new(7, 8)), &poly), 4.into());
+    // cuts the corner at (5, 5): the segment must move by 1 / sqrt(2) to clear it
+    assert_eq!(segment_outside_dist2((Pt::new(4, 10), Pt::new(10, 4)), &poly), Ratio::new(2, 4));
+    // endpoint outside
+    assert_eq!(segment_outside_dist2((Pt::new(0, 0), Pt::new(7, 7)), &poly), 4.into());
+    // leaves the hole only through vertices, no edge is crossed
+    assert_eq!(segment_outside_dist2((Pt::new(5, 10), Pt::new(10, 5)), &poly), Ratio::zero());
+}
+
+pub fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+/// Similarity transform of the plane that is exact on the lattice:
+/// pt -> (m * pt + shift) / scale.
+///
+/// With scale == 1 these are the 8 lattice symmetries combined with
+/// integer translations, and they map lattice points to lattice points.
+/// Rotations by Pythagorean angles have scale > 1; they map a lattice point
+/// to a lattice point only when the division is exact, otherwise
+/// `apply` returns None and `apply_scaled` gives the exact image times scale.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Transform {
+    pub m: [[i64; 2]; 2],
+    pub shift: Pt,
+    pub scale: i64,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { m: [[1, 0], [0, 1]], shift: Pt::new(0, 0), scale: 1 }
+    }
+
+    pub fn translation(d: Pt) -> Transform {
+        Transform { shift: d, ..Transform::identity() }
+    }
+
+    /// Rotation by quarter_turns * 90 degrees around the origin,
+    /// preceded by the reflection x -> -x if `reflect`.
+    /// Positive turns go the same way as positive angles in rotate_point,
+    /// (x, y) -> (-y, x).
+    pub fn lattice_symmetry(quarter_turns: i32, reflect: bool) -> Transform {
+        let rot = match quarter_turns.rem_euclid(4) {
+            0 => [[1, 0], [0, 1]],
+            1 => [[0, -1], [1, 0]],
+            2 => [[-1, 0], [0, -1]],
+            3 => [[0, 1], [-1, 0]],
+            _ => unreachable!(),
+        };
+        let mut t = Transform { m: rot, ..Transform::identity() };
+        if reflect {
+            t.m[0][0] = -t.m[0][0];
+            t.m[1][0] = -t.m[1][0];
+        }
+        t
+    }
+
+    /// All 8 symmetries of the lattice around the origin, identity first.
+    pub fn lattice_symmetries() -> Vec<Transform> {
+        let mut result = vec![];
+        for &reflect in &[false, true] {
+            for quarter_turns in 0..4 {
+                result.push(Transform::lattice_symmetry(quarter_turns, reflect));
+            }
+        }
+        result
+    }
+
+    /// Rotation around the origin by the angle with cos = a / c, sin = b / c,
+    /// if (a, b, c) is a Pythagorean triple (signs of a and b pick the quadrant).
+    /// c is at most 2^20 so that images of points in range don't overflow.
+    pub fn pythagorean(a: i64, b: i64, c: i64) -> Option<Transform> {
+        if !(1..=1 << 20).contains(&c) || a.unsigned_abs() > c as u64 || b.unsigned_abs() > c as u64 {
+            return None;
+        }
+        if a * a + b * b != c * c {
+            return None;
+        }
+        Some(Transform { m: [[a, -b], [b, a]], shift: Pt::new(0, 0), scale: c })
+    }
+
+    /// Same transform, but with `pivot` playing the role of the origin.
+    pub fn around(self, pivot: Pt) -> Transform {
+        Transform::translation(pivot)
+            .compose(&self)
+            .compose(&Transform::translation(Pt::new(-pivot.x, -pivot.y)))
+    }
+
+    /// self after other: pt -> self(other(pt)).
+    pub fn compose(&self, other: &Transform) -> Transform {
+        let a = &self.m;
+        let b = &other.m;
+        let mut m = [[0; 2]; 2];
+        for i in 0..2 {
+            for j in 0..2 {
+                m[i][j] = a[i][0] * b[0][j] + a[i][1] * b[1][j];
+            }
+        }
+        let t = Transform::mul(a, other.shift);
+        let shift = Pt::new(
+            t.x + other.scale * self.shift.x,
+            t.y + other.scale * self.shift.y);
+        Transform { m, shift, scale: self.scale * other.scale }.reduced()
+    }
+
+    /// Inverse transform. Only defined for rotations and reflections
+    /// (possibly scaled by Pythagorean triples), which is all we construct.
+    pub fn inverse(&self) -> Transform {
+        let m = &self.m;
+        let s = self.scale;
+        // m^T * m == s^2 * I
+        assert_eq!(m[0][0] * m[0][0] + m[1][0] * m[1][0], s * s);
+        assert_eq!(m[0][0] * m[0][1] + m[1][0] * m[1][1], 0);
+        let mt = [[m[0][0], m[1][0]], [m[0][1], m[1][1]]];
+        let t = Transform::mul(&mt, self.shift);
+        Transform {
+            m: [[s * mt[0][0], s * mt[0][1]], [s * mt[1][0], s * mt[1][1]]],
+            shift: Pt::new(-t.x, -t.y),
+            scale: s * s,
+        }.reduced()
+    }
+
+    fn mul(m: &[[i64; 2]; 2], pt: Pt) -> Pt {
+        Pt::new(m[0][0] * pt.x + m[0][1] * pt.y, m[1][0] * pt.x + m[1][1] * pt.y)
+    }
+
+    fn reduced(self) -> Transform {
+        let mut g = self.scale;
+        for row in &self.m {
+            for &x in row {
+                g = gcd(g, x);
+            }
+        }
+        g = gcd(gcd(g, self.shift.x), self.shift.y);
+        Transform {
+            m: [[self.m[0][0] / g, self.m[0][1] / g], [self.m[1][0] / g, self.m[1][1] / g]],
+            shift: Pt::new(self.shift.x / g, self.shift.y / g),
+            scale: self.scale / g,
+        }
+    }
+
+    /// Exact image of the point, multiplied by scale.
+    pub fn apply_scaled(&self, pt: Pt) -> Pt {
+        Transform::mul(&self.m, pt) + self.shift
+    }
+
+    /// Image of the point, if it's a lattice point.
+    pub fn apply(&self, pt: Pt) -> Option<Pt> {
+        let p = self.apply_scaled(pt);
+        if p.x % self.scale == 0 && p.y % self.scale == 0 {
+            Some(Pt::new(p.x / self.scale, p.y / self.scale))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_transform() {
+    let pts = [Pt::new(0, 0), Pt::new(3, 1), Pt::new(-2, 7), Pt::new(5, -4)];
+    let pivot = Pt::new(10, -3);
+    let syms = Transform::lattice_symmetries();
+    for (i, t) in syms.iter().enumerate() {
+        for t2 in &syms[..i] {
+            assert_ne!(t, t2);
+        }
+        let t = t.around(pivot).compose(&Transform::translation(Pt::new(1, 2)));
+        assert_eq!(t.scale, 1);
+        let inv = t.inverse();
+        for &p in &pts {
+            for &q in &pts {
+                assert_eq!(t.apply(p).unwrap().dist2(t.apply(q).unwrap()), p.dist2(q));
+            }
+            assert_eq!(inv.apply(t.apply(p).unwrap()), Some(p));
+            assert_eq!(t.compose(&inv).apply(p), Some(p));
+        }
+    }
+    assert_eq!(Transform::lattice_symmetry(1, false).around(pivot).apply(Pt::new(0, 0)),
+               Some(rotate_point(Pt::new(0, 0), pivot, 90)));
+    assert_eq!(Transform::lattice_symmetry(0, true).apply(Pt::new(3, 1)), Some(Pt::new(-3, 1)));
+
+    assert_eq!(Transform::pythagorean(3, 4, 6), None);
+    assert_eq!(Transform::pythagorean(-3, -4, -5), None);
+    assert_eq!(Transform::pythagorean(i64::MAX, 0, i64::MAX), None);
+    assert_eq!(Transform::pythagorean(i64::MIN, 3, 5), None);
+    let t = Transform::pythagorean(3, 4, 5).unwrap().around(pivot);
+    assert_eq!(t.apply(pivot + Pt::new(5, 0)), Some(pivot + Pt::new(3, 4)));
+    assert_eq!(t.apply(pivot + Pt::new(1, 0)), None);
+    let p = pivot + Pt::new(-2, 11);
+    assert_eq!(t.inverse().apply(t.apply(p).unwrap()), Some(p));
+    assert_eq!(t.apply(p).unwrap().dist2(pivot), p.dist2(pivot));
+    assert_eq!(t.compose(&t.inverse()), Transform::identity());
+}
+
 // Rotate a point around another point
 pub fn rotate_point(pt: Pt, pivot: Pt, angle: i16) -> Pt {
     let rad = angle as f64 * std::f64::consts::PI / 180.0;
@@ -530,7 +963,7 @@ fn test_rotate_poly() {
 #[cfg(test)]
 #[test]
 fn test_bounding_box() {
-    assert_eq!(bounding_box(&vec!{}), None);
-    assert_eq!(bounding_box(&vec!{Pt::new(1, 3)}), Some((Pt::new(1, 3), Pt::new(1,3))));
-    assert_eq!(bounding_box(&vec!{Pt::new(1, 3), Pt::new(0, 4)}), Some((Pt::new(0, 3), Pt::new(1,4))));
+    assert_eq!(bounding_box(&[]), None);
+    assert_eq!(bounding_box(&[Pt::new(1, 3)]), Some((Pt::new(1, 3), Pt::new(1,3))));
+    assert_eq!(bounding_box(&[Pt::new(1, 3), Pt::new(0, 4)]), Some((Pt::new(0, 3), Pt::new(1,4))));
 }
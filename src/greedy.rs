@@ -1,7 +1,7 @@
 #![allow(unused_imports)]
 
 use rand::Rng;
-use crate::checker::{length_range, check_pose, get_dislikes};
+use crate::checker::{length_range, check_pose, get_dislikes, IncrementalPose};
 use crate::geom::*;
 use crate::prelude::*;
 use crate::graph::*;
@@ -12,22 +12,20 @@ use ndarray::Array2;
 // Quick & dirty code reuse.
 use crate::threshold::{orig_distance, deformation_limits, HoleChecker, valid_positions};
 
-fn expand(problem: &Problem, vs: &mut Vec<Pt>, selected_idxs: &[usize], hole_checker: &HoleChecker) {
-    let mut cur_dislikes = get_dislikes(problem, vs);
+fn expand(problem: &Problem, pose: &mut IncrementalPose, selected_idxs: &[usize], hole_checker: &HoleChecker) {
+    let mut cur_dislikes = pose.dislikes;
     let mut prev_dislikes = cur_dislikes;
     loop {
         //dbg!(cur_dislikes);
         for idx in selected_idxs.iter() {
             //dbg!(idx);
-            let positions = valid_positions(problem, vs, *idx, hole_checker);
+            let positions = valid_positions(problem, &pose.vertices, *idx, hole_checker);
             for pt in positions {
-                let cur = vs[*idx];
-                vs[*idx] = pt;
-                let dislikes = get_dislikes(problem, vs);
-                if dislikes < cur_dislikes {
-                    cur_dislikes = dislikes;
+                pose.move_vertex(*idx, pt);
+                if pose.dislikes < cur_dislikes {
+                    cur_dislikes = pose.dislikes;
                 } else {
-                    vs[*idx] = cur;
+                    pose.undo();
                 }
             }
         }
@@ -39,26 +37,30 @@ fn expand(problem: &Problem, vs: &mut Vec<Pt>, selected_idxs: &[usize], hole_che
     }
 }
 
-fn shake(problem: &Problem, vs: &mut Vec<Pt>, selected_idxs: &[usize], rng:  &mut dyn rand::RngCore, hole_checker: &HoleChecker) -> i64 {
-    let cur_dislikes = get_dislikes(problem, vs);
+fn shake(problem: &Problem, pose: &mut IncrementalPose, selected_idxs: &[usize], rng:  &mut dyn rand::RngCore, hole_checker: &HoleChecker) -> i64 {
+    let cur_dislikes = pose.dislikes;
     for _ in 0..1 {
         for idx in selected_idxs.iter() {
             //dbg!(idx);
-            let perturbations = valid_positions(problem, vs, *idx, hole_checker);
+            let perturbations = valid_positions(problem, &pose.vertices, *idx, hole_checker);
+            // Moving a vertex that is not the nearest to any hole vertex can't add dislikes.
+            if pose.nearest_hole_vertices(*idx).next().is_none() {
+                if let Some(&pt) = perturbations.choose(rng) {
+                    pose.move_vertex(*idx, pt);
+                }
+                continue;
+            }
             let mut non_worsening_perturbations = vec![];
-            let cur = vs[*idx];
             for pt in perturbations {
-                vs[*idx] = pt;
-                let dislikes = get_dislikes(problem, vs);
-                if dislikes <= cur_dislikes {
+                pose.move_vertex(*idx, pt);
+                if pose.dislikes <= cur_dislikes {
                     non_worsening_perturbations.push(pt);
                 }
+                pose.undo();
             }
             // Actualy this shouldn't be empty because current position is in it.
-            if non_worsening_perturbations.is_empty() {
-                vs[*idx] = cur;
-            } else {
-                vs[*idx] = *non_worsening_perturbations.choose(rng).unwrap();
+            if let Some(&pt) = non_worsening_perturbations.choose(rng) {
+                pose.move_vertex(*idx, pt);
             }
 
         }
@@ -66,7 +68,7 @@ fn shake(problem: &Problem, vs: &mut Vec<Pt>, selected_idxs: &[usize], rng:  &mu
     cur_dislikes
 }
 
-pub fn greedy_shake(r: &ShakeRequest) -> Vec<Pt> {
+pub fn greedy_shake(r: &ShakeRequest) -> Result<Vec<Pt>, String> {
     //assert!(r.problem.bonuses.is_empty());
     //dbg!(r.problem.figure.vertices.len(), r.problem.hole.len());
     let mut selected = r.selected.clone();
@@ -79,27 +81,28 @@ pub fn greedy_shake(r: &ShakeRequest) -> Vec<Pt> {
             selected_idxs.push(i);
         }
     }
-    let mut rng = rand::thread_rng();
+    let mut rng = r.rng();
     
     let hole_checker = HoleChecker::new(&r.problem);
 
-    let mut cur_vs = r.vertices.clone();
-
-    let pr = check_pose(&r.problem, &Pose { vertices: cur_vs.clone(), bonuses: vec![] });
-    if !pr.valid {
-        dbg!("invalid pose passed to greedy shake");
-        return cur_vs;
+    let mut pose = IncrementalPose::new(&r.problem, r.vertices.clone());
+    if !pose.is_valid() {
+        return Err(crate::shake::invalid_start(r));
     }
-    let mut dislikes = pr.dislikes;
+    let mut dislikes = pose.dislikes;
     let convergence_cutoff = r.param*50;
     let mut i = 0;
-    loop {
+    let mut iteration = 0;
+    // never gets worse, so the current pose is the best one
+    while !r.stop.should_stop() {
         //if i % 10 == 0 {
         //    dbg!(i);
         //}
-        expand(&r.problem, &mut cur_vs, &selected_idxs, &hole_checker);
+        expand(&r.problem, &mut pose, &selected_idxs, &hole_checker);
         //dbg!("Shake");
-        let cur_dislikes = shake(&r.problem, &mut cur_vs, &selected_idxs, &mut rng, &hole_checker);
+        let cur_dislikes = shake(&r.problem, &mut pose, &selected_idxs, &mut rng, &hole_checker);
+        r.progress.report(iteration, Some(cur_dislikes as f64), Some(cur_dislikes.min(dislikes) as f64), Some(pose.violations));
+        iteration += 1;
         if cur_dislikes < dislikes {
             dislikes = cur_dislikes;
             i = 0;
@@ -111,5 +114,5 @@ pub fn greedy_shake(r: &ShakeRequest) -> Vec<Pt> {
         }
     }
 
-    cur_vs
+    Ok(pose.vertices)
 }
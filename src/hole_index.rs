@@ -0,0 +1,136 @@
+// Uniform grid over hole edges.
+// Answers the same queries as pt_in_poly and segment_in_poly,
+// but only looks at the hole edges near the query.
+
+use crate::prelude::*;
+use crate::geom::{poly_edges, pt_in_scaled_edges, segment_in_edges, BBox};
+
+pub struct HoleIndex {
+    pub hole: Vec<Pt>,
+    pub bbox: BBox,
+    edges: Vec<(Pt, Pt)>,
+    cell_size: i64,
+    nx: i64,
+    ny: i64,
+    // edge indices whose bounding box overlaps the cell, row-major
+    cells: Vec<Vec<u32>>,
+    // edge indices whose y-range overlaps the row of cells
+    rows: Vec<Vec<u32>>,
+}
+
+impl HoleIndex {
+    pub fn new(hole: &[Pt]) -> HoleIndex {
+        let bbox = BBox::from_pts(hole);
+        let edges: Vec<(Pt, Pt)> = poly_edges(hole).collect();
+
+        let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y) + 1;
+        let cells_per_side = 2 * (edges.len() as f64).sqrt().ceil() as i64;
+        let cell_size = (size + cells_per_side - 1) / cells_per_side;
+        let nx = (bbox.max_x - bbox.min_x) / cell_size + 1;
+        let ny = (bbox.max_y - bbox.min_y) / cell_size + 1;
+
+        let mut index = HoleIndex {
+            hole: hole.to_owned(),
+            bbox,
+            edges,
+            cell_size,
+            nx,
+            ny,
+            cells: vec![vec![]; (nx * ny) as usize],
+            rows: vec![vec![]; ny as usize],
+        };
+        for (i, &(a, b)) in index.edges.iter().enumerate() {
+            let (cx1, cy1) = index.cell(Pt::new(a.x.min(b.x), a.y.min(b.y)));
+            let (cx2, cy2) = index.cell(Pt::new(a.x.max(b.x), a.y.max(b.y)));
+            for cy in cy1..=cy2 {
+                index.rows[cy as usize].push(i as u32);
+                for cx in cx1..=cx2 {
+                    index.cells[(cy * nx + cx) as usize].push(i as u32);
+                }
+            }
+        }
+        index
+    }
+
+    // Cell containing the point, clamped to the grid.
+    fn cell(&self, pt: Pt) -> (i64, i64) {
+        let cx = (pt.x - self.bbox.min_x).div_euclid(self.cell_size).clamp(0, self.nx - 1);
+        let cy = (pt.y - self.bbox.min_y).div_euclid(self.cell_size).clamp(0, self.ny - 1);
+        (cx, cy)
+    }
+
+    fn scaled_in_bbox(&self, pt: Pt, scale: i64) -> bool {
+        self.bbox.min_x * scale <= pt.x && pt.x <= self.bbox.max_x * scale &&
+        self.bbox.min_y * scale <= pt.y && pt.y <= self.bbox.max_y * scale
+    }
+
+    // Is pt/scale inside the hole (boundary included)?
+    fn pt_in_scaled(&self, pt: Pt, scale: i64) -> bool {
+        if !self.scaled_in_bbox(pt, scale) {
+            return false;
+        }
+        // Row of pt.y / scale, rounded down. When pt.y / scale is not an integer
+        // it still lies strictly inside this row, so all relevant edges are there.
+        let (_, cy) = self.cell(Pt::new(self.bbox.min_x, pt.y.div_euclid(scale)));
+        let row = &self.rows[cy as usize];
+        pt_in_scaled_edges(pt, scale, row.iter().map(|&i| self.edges[i as usize]))
+    }
+
+    /// Same as pt_in_poly(pt, hole).
+    pub fn pt_in_hole(&self, pt: Pt) -> bool {
+        self.pt_in_scaled(pt, 1)
+    }
+
+    /// Same as segment_in_poly(seg, hole).
+    pub fn segment_in_hole(&self, seg: (Pt, Pt)) -> bool {
+        if !self.pt_in_hole(seg.0) || !self.pt_in_hole(seg.1) {
+            return false;
+        }
+        let (cx1, cy1) = self.cell(Pt::new(seg.0.x.min(seg.1.x), seg.0.y.min(seg.1.y)));
+        let (cx2, cy2) = self.cell(Pt::new(seg.0.x.max(seg.1.x), seg.0.y.max(seg.1.y)));
+        let mut candidates = vec![];
+        for cy in cy1..=cy2 {
+            for cx in cx1..=cx2 {
+                candidates.extend_from_slice(&self.cells[(cy * self.nx + cx) as usize]);
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        segment_in_edges(
+            seg,
+            candidates.iter().map(|&i| self.edges[i as usize]),
+            |pt, scale| self.pt_in_scaled(pt, scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use crate::geom::{pt_in_poly, segment_in_poly};
+    use super::*;
+
+    #[test]
+    fn same_as_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for problem_id in [1, 9, 31, 90, 106] {
+            let hole = load_problem(problem_id).hole;
+            let index = HoleIndex::new(&hole);
+            let b = index.bbox;
+            let mut random_pt = || {
+                if rng.gen_bool(0.3) {
+                    hole[rng.gen_range(0..hole.len())]
+                } else {
+                    Pt::new(rng.gen_range(b.min_x - 2..=b.max_x + 2),
+                            rng.gen_range(b.min_y - 2..=b.max_y + 2))
+                }
+            };
+            for _ in 0..2000 {
+                let p1 = random_pt();
+                let p2 = random_pt();
+                assert_eq!(index.pt_in_hole(p1), pt_in_poly(p1, &hole), "{:?}", p1);
+                assert_eq!(index.segment_in_hole((p1, p2)), segment_in_poly((p1, p2), &hole),
+                           "{} {:?} {:?}", problem_id, p1, p2);
+            }
+        }
+    }
+}
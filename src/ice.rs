@@ -2,7 +2,8 @@
 
 use rand::Rng;
 use crate::checker::Checker;
-use crate::geom::{segment_in_poly, bounding_box, pt_in_poly};
+use crate::geom::{segment_in_poly, segment_outside_dist2};
+use crate::triangulation::Triangulation;
 use crate::prelude::*;
 use crate::shake::ShakeRequest;
 
@@ -39,37 +40,29 @@ pub fn ice_shake(r: &ShakeRequest) -> Vec<Pt> {
     for &ei in &edge_infos {
         e_total = e_total + ei;
     }
-    dbg!(e_total);
 
-    let mut score = e_total.hole_penalty as f64 + e_total.length_penalty;
+    let mut score = e_total.score();
 
     let mut best_pose = pose.clone();
     let mut best_score = score;
     let mut best_edge_infos = edge_infos.clone();
     let mut best_e_total = e_total;
 
-    let mut rng = rand::thread_rng();
+    let mut rng = r.rng();
     let num_steps = 500_000;
 
-    let (pt_min, pt_max) = bounding_box(&r.problem.hole).unwrap();
-    let mut inside = vec![];
-    for x in pt_min.x..=pt_max.x {
-        for y in pt_min.y..=pt_max.y {
-            let pt = Pt::new(x, y);
-            if pt_in_poly(pt, &r.problem.hole) {
-                inside.push(pt);
-            }
-        }
-    }
+    let triangulation = Triangulation::new(&r.problem.hole);
 
     for step in 0..num_steps {
-        let threshold = (num_steps - step) as f64 / num_steps as f64;
-        if step % (num_steps / 10 + 1) == 0 {
-            eprintln!("{} {} {}", step, threshold, score);
+        if step % 1000 == 0 {
+            r.progress.report(step as u64, Some(score), Some(best_score), Some(e_total.violations as usize));
+            if r.stop.should_stop() {
+                break;
+            }
         }
+        let threshold = (num_steps - step) as f64 / num_steps as f64;
 
         if rng.gen_range(0..num_steps/10) == 0 {
-            eprintln!("reset @ {}", step);
             score = best_score;
             pose = best_pose.clone();
             edge_infos = best_edge_infos.clone();
@@ -84,7 +77,7 @@ pub fn ice_shake(r: &ShakeRequest) -> Vec<Pt> {
             x: old_pt.x + rng.gen_range(-1..=1),
             y: old_pt.y + rng.gen_range(-1..=1),
         };*/
-        let new_pt = inside[rng.gen_range(0..inside.len())];
+        let new_pt = triangulation.random_point(&mut rng);
         pose.vertices[v_idx] = new_pt;
 
         let old_e_total = e_total;
@@ -103,7 +96,7 @@ pub fn ice_shake(r: &ShakeRequest) -> Vec<Pt> {
             edge_infos[e_idx] = new_info;
         }
 
-        let new_score = e_total.hole_penalty as f64 + e_total.length_penalty;
+        let new_score = e_total.score();
 
         if new_score < score + threshold {
             pose.vertices[v_idx] = old_pt;
@@ -113,7 +106,6 @@ pub fn ice_shake(r: &ShakeRequest) -> Vec<Pt> {
                 best_pose = pose.clone();
                 best_edge_infos = edge_infos.clone();
                 best_e_total = e_total;
-                eprintln!("{}, {} {:?}", step, best_score, e_total);
             }
         } else {
             e_total = old_e_total;
@@ -128,7 +120,10 @@ pub fn ice_shake(r: &ShakeRequest) -> Vec<Pt> {
 #[derive(Debug, Clone, Copy)]
 struct EdgeInfo {
     hole_penalty: i32,
+    // how far the edge is outside the hole, relative to its length
+    hole_distance_penalty: f64,
     length_penalty: f64,
+    violations: i32,
 }
 
 impl std::ops::Add for EdgeInfo {
@@ -137,7 +132,9 @@ impl std::ops::Add for EdgeInfo {
     fn add(self, rhs: Self) -> Self::Output {
         EdgeInfo {
             hole_penalty: self.hole_penalty + rhs.hole_penalty,
+            hole_distance_penalty: self.hole_distance_penalty + rhs.hole_distance_penalty,
             length_penalty: self.length_penalty + rhs.length_penalty,
+            violations: self.violations + rhs.violations,
         }
     }
 }
@@ -148,7 +145,9 @@ impl std::ops::Sub for EdgeInfo {
     fn sub(self, rhs: Self) -> Self::Output {
         EdgeInfo {
             hole_penalty: self.hole_penalty - rhs.hole_penalty,
+            hole_distance_penalty: self.hole_distance_penalty - rhs.hole_distance_penalty,
             length_penalty: self.length_penalty - rhs.length_penalty,
+            violations: self.violations - rhs.violations,
         }
     }
 }
@@ -157,23 +156,38 @@ impl EdgeInfo {
     fn zero() -> EdgeInfo {
         EdgeInfo {
             hole_penalty: 0,
+            hole_distance_penalty: 0.0,
             length_penalty: 0.0,
+            violations: 0,
         }
     }
+    fn score(&self) -> f64 {
+        self.hole_penalty as f64 + self.hole_distance_penalty + self.length_penalty
+    }
     fn new(checker: &mut Checker, edge_idx: usize, pt1: Pt, pt2: Pt) -> Self {
         let (min_d, max_d, _) = checker.edge_ranges[edge_idx];
+        // penalties are relative to the edge length, zero-length edges count as length 1
+        let scale = max_d.max(1) as f64;
         let d = pt1.dist2(pt2);
         let length_penalty = if d < min_d {
-            (min_d - d) as f64 / max_d as f64
+            (min_d - d) as f64 / scale
         } else if d > max_d {
-            (d - max_d) as f64 / max_d as f64
+            (d - max_d) as f64 / scale
         } else {
             0.0
         };
         assert!(length_penalty >= 0.0);
+        let (hole_penalty, hole_distance_penalty) = if checker.edge_in_hole(pt1, pt2) {
+            (0, 0.0)
+        } else {
+            let d = segment_outside_dist2((pt1, pt2), &checker.problem.hole);
+            (1, d.to_f64() / scale)
+        };
         EdgeInfo {
-            hole_penalty: if checker.edge_in_hole(pt1, pt2) { 0 } else { 1 },
+            hole_penalty,
+            hole_distance_penalty,
             length_penalty,
+            violations: (hole_penalty > 0 || length_penalty > 0.0) as i32,
         }
     }
 }
@@ -187,9 +201,27 @@ fn ice_demo() {
         selected: vec![true; p.figure.vertices.len()],
         method: "ice".to_owned(),
         param: 42,
+        seed: crate::util::flag_value("seed").map(|s| s.parse().unwrap()),
+        stop: Default::default(),
+        progress: crate::progress::Progress::new("ice").with_sink(crate::progress::StderrSink::default()),
         problem: p,
     };
     let start = std::time::Instant::now();
     ice_shake(&r);
     eprintln!("it took {} s", start.elapsed().as_secs_f64());
 }
+
+#[cfg(test)]
+#[test]
+fn test_zero_length_edge() {
+    let p = Problem {
+        bonuses: vec![],
+        hole: vec![Pt::new(0, 0), Pt::new(10, 0), Pt::new(10, 10), Pt::new(0, 10)],
+        figure: Figure { vertices: vec![Pt::new(1, 1), Pt::new(1, 1)], edges: vec![(0, 1)] },
+        epsilon: 0,
+    };
+    let mut checker = Checker::new(&p, &[], 2);
+    let info = EdgeInfo::new(&mut checker, 0, Pt::new(5, 5), Pt::new(20, 5));
+    assert!(info.score().is_finite() && info.score() > 1.0, "{:?}", info);
+    assert_eq!(EdgeInfo::new(&mut checker, 0, Pt::new(5, 5), Pt::new(5, 5)).score(), 0.0);
+}
@@ -1,24 +1,35 @@
 mod banana;
 mod bonus_graph;
 mod bruteforce;
+mod cancel;
+mod candidates;
+mod canonical;
 mod checker;
+mod conformance;
 mod daiquiri;
 mod db;
 mod dev_server;
 mod domain_model;
 mod example;
+mod generator;
 mod geom;
 mod graph;
 mod greedy;
+mod hole_index;
 mod ice;
 mod mango;
 mod multishaker;
+mod nearest;
 mod planner;
 mod poses_live;
 mod prelude;
+mod progress;
 mod rail_constrained;
 mod rail;
 mod random;
+mod reference;
+mod registry;
+mod render;
 mod rle;
 mod rotate;
 mod scratch_manpages;
@@ -29,7 +40,9 @@ mod springs;
 mod submitter;
 mod summary;
 mod threshold;
+mod triangulation;
 mod util;
+mod visibility;
 mod viz;
 
 #[linkme::distributed_slice]
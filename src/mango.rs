@@ -8,6 +8,7 @@ use std::cmp::max;
 use crate::geom::{segment_in_poly, bounding_box, BBox};
 use rand::prelude::SliceRandom;
 use crate::checker::Checker;
+use crate::candidates::candidates;
 
 struct Borders {
     min_x: i64,
@@ -27,53 +28,19 @@ fn deformation_limits(problem: &Problem, v1_id: usize, v2_id: usize) -> (i64, i6
 }
 
 pub fn available_positions(checker: &mut Checker, vertices: &[Option<Pt>], v_id: usize) -> Vec<Pt> {
-    let mut available_positions = vec![];
-
-    let neigbours = checker.neighbours(v_id).clone();
-    let bbox = checker.bbox;
-
-    for n_id in &neigbours {
-        if let Some(n) = vertices[*n_id] {
-            let (_, max_dist) = deformation_limits(&checker.problem, v_id, *n_id);
-            bbox.intersect(&BBox {
-                min_x: n.x - max_dist,
-                max_x: n.x + max_dist,
-                min_y: n.y - max_dist,
-                max_y: n.y + max_dist,
-            });
-        }
-    }
-
-    for x in bbox.min_x..=bbox.max_x {
-        'l: for y in bbox.min_y..=bbox.max_y {
-            let assumed_pos = Pt { x, y };
-
-            if Some(assumed_pos) == vertices[v_id] {
-                continue 'l;
-            }
-
-            for n_id in &neigbours {
-                if let Some(n) = vertices[*n_id] {
-                    let (min_dist, max_dist) = deformation_limits(&checker.problem, v_id, *n_id);
-                    let new_dist = assumed_pos.dist2(n);
-                    if !(min_dist <= new_dist && new_dist <= max_dist) {
-                        // eprintln!("Drop {:?} by distance", assumed_pos);
-                        continue 'l;
-                    }
-                    if !segment_in_poly((n, assumed_pos), &checker.problem.hole) {
-                        // eprintln!("Drop {:?} by intersection", assumed_pos);
-                        continue 'l;
-                    }
-                }
-            }
-            available_positions.push(assumed_pos);
-        }
-    }
-    available_positions
+    let placed: Vec<(Pt, i64, i64)> = checker.neighbours(v_id).clone().into_iter()
+        .filter_map(|n_id| vertices[n_id].map(|n| {
+            let (min_dist, max_dist) = deformation_limits(&checker.problem, v_id, n_id);
+            (n, min_dist, max_dist)
+        }))
+        .collect();
+    candidates(&checker.hole_index, &checker.inside, &placed).as_points().into_iter()
+        .filter(|&pt| Some(pt) != vertices[v_id])
+        .collect()
 }
 
 pub fn mango_shake(r: &ShakeRequest) -> Vec<Pt> {
-    let rng = &mut rand::thread_rng();
+    let rng = &mut r.rng();
     let mut result: Vec<_> = r.vertices.iter().map(|pt| Some(*pt)).collect();
     let mut selected_idxs: Vec<_> = r.selected.iter().enumerate()
         .filter(|(_, b)| **b)
@@ -85,15 +52,20 @@ pub fn mango_shake(r: &ShakeRequest) -> Vec<Pt> {
     let mut checker = Checker::new(&r.problem, &[], r.problem.figure.vertices.len());
     loop {
         selected_idxs.shuffle(rng);
+        // score is the number of vertices with nowhere to go
+        let mut stuck = 0;
         for v_id in &selected_idxs {
             let available_positions = available_positions(&mut checker, &result, *v_id);
             if !available_positions.is_empty() {
                 result[*v_id] = Some(*available_positions.choose(rng).unwrap());
                 success = true;
+            } else {
+                stuck += 1;
             }
         }
+        r.progress.report(iteration_count, Some(stuck as f64), None, None);
         iteration_count += 1;
-        if success || iteration_count > 10 {
+        if success || iteration_count > 10 || r.stop.should_stop() {
             return result.iter().map(|pt| pt.unwrap()).collect();
         }
     }
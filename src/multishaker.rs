@@ -1,4 +1,4 @@
-use rand::Rng;
+use rand::prelude::*;
 //use rand::seq::SliceRandom;
 use crate::prelude::*;
 use crate::checker::{check_pose};
@@ -8,10 +8,12 @@ use crate::shake::ShakeRequest;
 use crate::geom::pt_in_poly;
 use crate::greedy;
 use crate::threshold;
+use crate::solver::{best_found, start_vertices, Solver, SolverError};
+use crate::cancel::CancelToken;
+use crate::progress::Progress;
 
 fn tuck(p: &Problem, pts: &mut [Pt], rng: &mut dyn rand::RngCore) -> bool {
     loop {
-        eprintln!("Tucking vertices...");
         let mut vertices_inside = vec![];
         let mut vertices_outside = vec![];
         for (idx, pt) in pts.iter().enumerate() {
@@ -23,12 +25,8 @@ fn tuck(p: &Problem, pts: &mut [Pt], rng: &mut dyn rand::RngCore) -> bool {
         }
         assert!(!vertices_inside.is_empty());
         let pt_inside = pts[vertices_inside[rng.gen_range(0..vertices_inside.len())]];
-        if !vertices_outside.is_empty() {
-            eprintln!("Vertices found.");
-            for idx_outside in vertices_outside {
-                pts[idx_outside] = pt_inside;
-                eprintln!("moving {} to {}", idx_outside, idx_outside);
-            }
+        for idx_outside in vertices_outside {
+            pts[idx_outside] = pt_inside;
         }
 
         // No new points will appear on the ouside now. But new offending edges might appear.
@@ -36,17 +34,13 @@ fn tuck(p: &Problem, pts: &mut [Pt], rng: &mut dyn rand::RngCore) -> bool {
             let pose = Pose{vertices: pts.to_vec(), bonuses: vec![]};
             let response = check_pose(p, &pose);
             if response.valid {
-                eprintln!("valid");
                 return true;
             }
             let bad_edges: Vec<usize> = (0..response.edges.len()).filter(|e| !response.edge_statuses[*e].fits_in_hole).collect();
             if bad_edges.is_empty() {
-                eprintln!("no bad edges");                
                 return false;
             }
-            eprintln!("Tucking edges...");
             for edge in bad_edges {
-                eprintln!("moving {}", edge);
                 let (idx1, idx2) = response.edges[edge];
                 pts[idx1] = pt_inside;
                 pts[idx2] = pt_inside;
@@ -67,19 +61,42 @@ fn center_of_mass(pts: &[Pt]) -> Pt {
 crate::entry_point!("multishaker", multishaker);
 fn multishaker() {
     let problem_id: i32 = std::env::args().nth(2).unwrap().parse().unwrap();
-    let aggressive = std::env::args().nth(3) != None;
+    let aggressive = std::env::args().nth(3).is_some_and(|a| !a.starts_with("--"));
     if aggressive {
         eprintln!("Applying aggressive transformations");
     }
 
-    let mut submitter = Submitter::new(problem_id, "multishaker".to_string());
+    let seed = crate::util::seed_flag();
+
+    let mut submitter = Submitter::new(problem_id, format!("multishaker seed={}", seed));
 
     
     let p = load_problem(problem_id);
+    multishake(&p, p.figure.vertices.clone(), aggressive, seed, &CancelToken::default(), &mut |pose| {
+        submitter.update(&p, pose);
+        true
+    });
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MultishakerSolver {
+    pub aggressive: bool,
+    pub seed: Option<u64>,
+}
 
-    let mut rng = rand::thread_rng();
+impl Solver for MultishakerSolver {
+    fn solve(&mut self, problem: &Problem, start: Option<&Pose>, stop: &CancelToken, progress: &Progress) -> Result<Pose, SolverError> {
+        let pts = start_vertices(problem, start)?;
+        let seed = self.seed.unwrap_or_else(rand::random);
+        best_found(problem, stop, progress, |on_pose| multishake(problem, pts, self.aggressive, seed, stop, on_pose))
+    }
+}
 
-    let mut pts = p.figure.vertices.clone();
+/// Tucks the pose into the hole, then alternates greedy and threshold shakes,
+/// reporting the pose after each, until stopped or until on_pose() says stop.
+pub fn multishake(p: &Problem, mut pts: Vec<Pt>, aggressive: bool, seed: u64, stop: &CancelToken, on_pose: &mut dyn FnMut(&Pose) -> bool) {
+    let mut rng = StdRng::seed_from_u64(seed);
 
     if aggressive {
         let cm_pts = center_of_mass(&pts);
@@ -90,21 +107,25 @@ fn multishaker() {
 
     // Make valid pose with daquiri.
     loop {
-        eprintln!("Tuck/mojito...");
-        let valid = tuck(&p, &mut pts, &mut rng);
+        if stop.should_stop() {
+            return;
+        }
+        let valid = tuck(p, &mut pts, &mut rng);
         if valid {
             break;
         }
         
         let max_mojito_iterations = 10;
         for _ in 0..max_mojito_iterations {
-            eprintln!("Mojito loop...");
             let request = ShakeRequest {
                 problem: p.clone(),
                 vertices: pts.clone(),
                 selected: vec![true; pts.len()],
                 method: "mojito".to_string(),
                 param: 5,
+                seed: Some(rng.gen()),
+                stop: stop.clone(),
+                progress: Default::default(),
             };
             let new_pts = daiquiri::daikuiri_shake(&request, true);
             if new_pts == pts {
@@ -117,29 +138,37 @@ fn multishaker() {
 
     loop {
         // Use greedy shaker.
-        eprintln!("Greedy...");
         let request = ShakeRequest {
             problem: p.clone(),
             vertices: pts.clone(),
             selected: vec![true; pts.len()],
             method: "greedy".to_string(),
             param: 2,
+            seed: Some(rng.gen()),
+            stop: stop.clone(),
+            progress: Default::default(),
         };
-        pts = greedy::greedy_shake(&request);
+        pts = greedy::greedy_shake(&request).expect("tuck() leaves a valid pose");
         let pose = Pose{vertices: pts.clone(), bonuses: vec![]};
-        submitter.update(&p, &pose);
+        if !on_pose(&pose) || stop.should_stop() {
+            return;
+        }
 
-        eprintln!("Threshold...");
         let request = ShakeRequest {
             problem: p.clone(),
             vertices: pts.clone(),
             selected: vec![true; pts.len()],
             method: "threshold".to_string(),
             param: 2,
+            seed: Some(rng.gen()),
+            stop: stop.clone(),
+            progress: Default::default(),
         };
-        pts = threshold::threshold_shake(&request);
+        pts = threshold::threshold_shake(&request).expect("greedy keeps the pose valid");
         let pose = Pose{vertices: pts.clone(), bonuses: vec![]};
-        submitter.update(&p, &pose);
+        if !on_pose(&pose) || stop.should_stop() {
+            return;
+        }
 
         // Use threshold shaker.
     }
@@ -0,0 +1,141 @@
+// Uniform grid over pose vertices for nearest-vertex queries
+// while the vertices move around.
+// Cells are hashed, so vertices may go anywhere.
+
+use std::collections::HashMap;
+use crate::prelude::*;
+use crate::geom::BBox;
+
+pub struct NearestIndex {
+    pts: Vec<Pt>,
+    cell_size: i64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    // covers every nonempty cell, but only grows
+    cell_bbox: BBox,
+}
+
+impl NearestIndex {
+    pub fn new(pts: &[Pt]) -> NearestIndex {
+        let (cell_size, cell_bbox) = if pts.is_empty() {
+            (1, BBox { min_x: 0, max_x: -1, min_y: 0, max_y: -1 })
+        } else {
+            // about one point per cell if they are spread evenly
+            let b = BBox::from_pts(pts);
+            let size = (b.max_x - b.min_x).max(b.max_y - b.min_y) + 1;
+            let cells_per_side = (pts.len() as f64).sqrt().ceil() as i64;
+            let cell_size = (size + cells_per_side - 1) / cells_per_side;
+            (cell_size, BBox {
+                min_x: b.min_x.div_euclid(cell_size),
+                max_x: b.max_x.div_euclid(cell_size),
+                min_y: b.min_y.div_euclid(cell_size),
+                max_y: b.max_y.div_euclid(cell_size),
+            })
+        };
+        let mut index = NearestIndex {
+            pts: pts.to_owned(),
+            cell_size,
+            cells: HashMap::new(),
+            cell_bbox,
+        };
+        for (i, &pt) in pts.iter().enumerate() {
+            let c = index.cell(pt);
+            index.cells.entry(c).or_default().push(i);
+        }
+        index
+    }
+
+    fn cell(&self, pt: Pt) -> (i64, i64) {
+        (pt.x.div_euclid(self.cell_size), pt.y.div_euclid(self.cell_size))
+    }
+
+    pub fn move_point(&mut self, i: usize, pt: Pt) {
+        let old = self.cell(self.pts[i]);
+        let new = self.cell(pt);
+        self.pts[i] = pt;
+        if old == new {
+            return;
+        }
+        let bucket = self.cells.get_mut(&old).unwrap();
+        bucket.retain(|&j| j != i);
+        if bucket.is_empty() {
+            self.cells.remove(&old);
+        }
+        self.cells.entry(new).or_default().push(i);
+        let b = &mut self.cell_bbox;
+        b.min_x = b.min_x.min(new.0);
+        b.max_x = b.max_x.max(new.0);
+        b.min_y = b.min_y.min(new.1);
+        b.max_y = b.max_y.max(new.1);
+    }
+
+    /// Squared distance to the nearest point and its index,
+    /// the smallest index among equally near ones. None if there are no points.
+    pub fn nearest(&self, q: Pt) -> Option<(i64, usize)> {
+        if self.pts.is_empty() {
+            return None;
+        }
+        let (cx, cy) = self.cell(q);
+        let b = &self.cell_bbox;
+        // every nonempty cell is within this many rings
+        let max_r = (cx - b.min_x).abs().max((cx - b.max_x).abs())
+            .max((cy - b.min_y).abs()).max((cy - b.max_y).abs());
+        let mut best: Option<(i64, usize)> = None;
+        for r in 0..=max_r {
+            // Points in ring r are at least this far in x or in y.
+            if let Some((d, _)) = best {
+                let gap = ((r - 1) * self.cell_size + 1).max(0);
+                if gap * gap > d {
+                    break;
+                }
+            }
+            for dy in -r..=r {
+                let step = if dy == -r || dy == r { 1 } else { 2 * r };
+                let mut dx = -r;
+                while dx <= r {
+                    if let Some(bucket) = self.cells.get(&(cx + dx, cy + dy)) {
+                        for &i in bucket {
+                            let cand = (self.pts[i].dist2(q), i);
+                            if best.is_none_or(|best| cand < best) {
+                                best = Some(cand);
+                            }
+                        }
+                    }
+                    dx += step.max(1);
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use super::*;
+
+    fn brute_force(pts: &[Pt], q: Pt) -> Option<(i64, usize)> {
+        pts.iter().enumerate().map(|(i, p)| (p.dist2(q), i)).min()
+    }
+
+    #[test]
+    fn same_as_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(NearestIndex::new(&[]).nearest(Pt::new(1, 2)), None);
+        for &(n, size) in &[(1, 10), (5, 3), (30, 100), (200, 50)] {
+            let rand_pt = |rng: &mut StdRng| Pt::new(rng.gen_range(-size..=size), rng.gen_range(-size..=size));
+            let mut pts: Vec<Pt> = (0..n).map(|_| rand_pt(&mut rng)).collect();
+            let mut index = NearestIndex::new(&pts);
+            for _ in 0..500 {
+                if rng.gen_bool(0.3) {
+                    let i = rng.gen_range(0..n);
+                    // sometimes far away
+                    let pt = if rng.gen_bool(0.1) { Pt::new(size * 7, -size * 5) } else { rand_pt(&mut rng) };
+                    index.move_point(i, pt);
+                    pts[i] = pt;
+                }
+                let q = Pt::new(rng.gen_range(-2 * size..=2 * size), rng.gen_range(-2 * size..=2 * size));
+                assert_eq!(index.nearest(q), brute_force(&pts, q));
+            }
+        }
+    }
+}
@@ -64,7 +64,6 @@ fn scrape_poses_demo() {
 
 crate::entry_point!("scrape_cache", scrape_cache, _EP4);
 fn scrape_cache() {
-    const COUNT: i32 = 132;  // 132
     let mut cache = match File::open(project_path("cache/server.cache")) {
         Ok(mut file) => {
             let mut buf = String::new();
@@ -79,7 +78,7 @@ fn scrape_cache() {
     cache.problems = HashMap::new();
 
     let mut scraper = Scraper::new();
-    for i in 1..=COUNT {
+    for i in all_problem_ids() {
         eprintln!("problem {}...", i);
         let p = scraper.problem_info(i).clone();
         cache.problems.insert(i, p.clone());
@@ -114,7 +113,7 @@ pub fn read_cache() -> ProblemCache {
 crate::entry_point!("resubmit_best", resubmit_best, _EP5);
 fn resubmit_best() {
     let data = read_cache();
-    for problem_id in all_problem_ids() {
+    for problem_id in crate::registry::selected_problem_ids() {
         let pi = data.problems.get(&problem_id).unwrap();
 
         let hs = bonusless_highscore(pi);
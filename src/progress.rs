@@ -0,0 +1,158 @@
+// How shakes and solvers are doing while they run, for convergence curves.
+// Methods call Progress::report() every so often; whoever started the run
+// decides where the events go: stderr, a JSONL file, or a channel that
+// viz_server hands out through /api/progress/<job>.
+
+use std::io::Write;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::prelude::*;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgressEvent {
+    pub solver: String,
+    pub iteration: u64,
+    pub elapsed_ms: u64,
+    // As the method measures it, lower is better. None if it has no score.
+    pub score: Option<f64>,
+    pub best: Option<f64>,
+    pub violations: Option<usize>,
+}
+
+pub trait ProgressSink: Send {
+    fn event(&mut self, e: &ProgressEvent);
+}
+
+struct Inner {
+    solver: String,
+    start: Instant,
+    sinks: Vec<Box<dyn ProgressSink>>,
+}
+
+/// Clones report to the same sinks. The default one reports nowhere.
+#[derive(Clone, Default)]
+pub struct Progress {
+    inner: Option<Arc<Mutex<Inner>>>,
+}
+
+impl Progress {
+    pub fn new(solver: &str) -> Progress {
+        Progress { inner: Some(Arc::new(Mutex::new(Inner {
+            solver: solver.to_string(),
+            start: Instant::now(),
+            sinks: vec![],
+        }))) }
+    }
+
+    pub fn with_sink(self, sink: impl ProgressSink + 'static) -> Progress {
+        if let Some(inner) = &self.inner {
+            inner.lock().unwrap().sinks.push(Box::new(sink));
+        }
+        self
+    }
+
+    pub fn report(&self, iteration: u64, score: Option<f64>, best: Option<f64>, violations: Option<usize>) {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut inner = inner.lock().unwrap();
+        let e = ProgressEvent {
+            solver: inner.solver.clone(),
+            iteration,
+            elapsed_ms: inner.start.elapsed().as_millis() as u64,
+            score,
+            best,
+            violations,
+        };
+        for sink in &mut inner.sinks {
+            sink.event(&e);
+        }
+    }
+}
+
+/// Human-readable, at most once a second unless the best score improves.
+#[derive(Default)]
+pub struct StderrSink {
+    last: Option<(Instant, Option<f64>)>,
+}
+
+impl ProgressSink for StderrSink {
+    fn event(&mut self, e: &ProgressEvent) {
+        if let Some((t, best)) = self.last {
+            let improved = match (e.best, best) {
+                (Some(b), Some(prev)) => b < prev,
+                (b, prev) => b.is_some() && prev.is_none(),
+            };
+            if !improved && t.elapsed() < Duration::from_secs(1) {
+                return;
+            }
+        }
+        self.last = Some((Instant::now(), e.best));
+        let opt = |x: Option<f64>| x.map_or("-".to_string(), |x| format!("{:.3}", x));
+        eprintln!("{} #{} {:.1}s: score {}, best {}, violations {}",
+                  e.solver, e.iteration, e.elapsed_ms as f64 / 1000.0,
+                  opt(e.score), opt(e.best), e.violations.map_or("-".to_string(), |v| v.to_string()));
+    }
+}
+
+/// One event per line, flushed right away so the file can be tailed.
+pub struct JsonlSink {
+    out: std::fs::File,
+}
+
+impl JsonlSink {
+    /// `path` is relative to the project root, like "outputs/progress/11_ice.jsonl".
+    pub fn create(path: &str) -> std::io::Result<JsonlSink> {
+        let path = project_path(path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(JsonlSink { out: std::fs::File::create(path)? })
+    }
+}
+
+impl ProgressSink for JsonlSink {
+    fn event(&mut self, e: &ProgressEvent) {
+        let mut line = serde_json::to_vec(e).unwrap();
+        line.push(b'\n');
+        self.out.write_all(&line).unwrap();
+    }
+}
+
+/// Nobody listening is fine, events are dropped then.
+pub struct ChannelSink(pub Sender<ProgressEvent>);
+
+impl ProgressSink for ChannelSink {
+    fn event(&mut self, e: &ProgressEvent) {
+        let _ = self.0.send(e.clone());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_progress() {
+    Progress::default().report(1, None, None, None);
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let path = "outputs/progress/test_progress.jsonl";
+    let progress = Progress::new("test")
+        .with_sink(ChannelSink(tx))
+        .with_sink(JsonlSink::create(path).unwrap())
+        .with_sink(StderrSink::default());
+    let clone = progress.clone();
+    progress.report(0, Some(3.0), Some(3.0), Some(2));
+    clone.report(1, Some(5.0), Some(3.0), None);
+    drop((progress, clone));
+
+    let events: Vec<ProgressEvent> = rx.iter().collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!((events[1].solver.as_str(), events[1].iteration, events[1].best), ("test", 1, Some(3.0)));
+    let lines: Vec<ProgressEvent> = std::fs::read_to_string(project_path(path)).unwrap()
+        .lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines, events);
+    std::fs::remove_file(project_path(path)).unwrap();
+    let _ = std::fs::remove_dir(project_path("outputs/progress"));
+}
@@ -1,22 +1,14 @@
-use rand::Rng;
+use rand::prelude::*;
 use crate::domain_model::BonusName;
-use crate::geom::{bounding_box, pt_in_poly};
+use crate::triangulation::Triangulation;
 use crate::prelude::*;
 use crate::checker::Checker;
 use crate::submitter::Submitter;
-
-fn deltas(min_d: i64, max_d: i64) -> Vec<Pt> {
-    let mut result = vec![];
-    for x in -max_d..=max_d {
-        for y in -max_d..=max_d {
-            let d = Pt { x, y }.len2();
-            if min_d <= d && d <= max_d {
-                result.push(Pt {x, y});
-            }
-        }
-    }
-    result
-}
+use crate::candidates::candidates;
+use crate::visibility::visible_from;
+use crate::solver::{best_found, Solver, SolverError};
+use crate::cancel::CancelToken;
+use crate::progress::Progress;
 
 crate::entry_point!("rail", rail);
 fn rail() {
@@ -26,12 +18,37 @@ fn rail() {
     match std::env::args().nth(3).as_deref() {
         Some("S") => superflex = true,
         None => {},
+        Some(s) if s.starts_with("--") => {},
         _ => panic!()
     };
+    let seed = crate::util::seed_flag();
 
-    let mut submitter = Submitter::new(problem_id, "rail".to_string());
+    let mut submitter = Submitter::new(problem_id, format!("rail seed={}", seed));
 
     let p = load_problem(problem_id);
+    rail_search(&p, superflex, seed, &CancelToken::default(), &mut |pose| {
+        submitter.update(&p, pose);
+        true
+    });
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RailSolver {
+    pub superflex: bool,
+    pub seed: Option<u64>,
+}
+
+impl Solver for RailSolver {
+    fn solve(&mut self, problem: &Problem, _start: Option<&Pose>, stop: &CancelToken, progress: &Progress) -> Result<Pose, SolverError> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        best_found(problem, stop, progress, |on_pose| rail_search(problem, self.superflex, seed, stop, on_pose))
+    }
+}
+
+/// Places vertices one by one, most constrained first, restarting on dead ends.
+/// Reports every complete pose until stopped or until on_pose() says stop.
+pub fn rail_search(p: &Problem, superflex: bool, seed: u64, stop: &CancelToken, on_pose: &mut dyn FnMut(&Pose) -> bool) {
     let mut bonuses = vec![];
     if superflex {
         bonuses.push(PoseBonus {
@@ -40,9 +57,8 @@ fn rail() {
             edge: None,
         });
     }
-    dbg!(&bonuses);
 
-    let mut checker = Checker::new(&p, &bonuses, p.figure.vertices.len());
+    let mut checker = Checker::new(p, &bonuses, p.figure.vertices.len());
     let edges = checker.edges.clone();
     let mut inci: Vec<Vec<usize>> = vec![vec![]; edges.len()];
     for (i, &(start, end)) in edges.iter().enumerate() {
@@ -50,24 +66,14 @@ fn rail() {
         inci[end].push(i);
     }
 
-    let (pt_min, pt_max) = bounding_box(&p.hole).unwrap();
-    let mut inside = vec![];
-    for x in pt_min.x..=pt_max.x {
-        for y in pt_min.y..=pt_max.y {
-            let pt = Pt::new(x, y);
-            if pt_in_poly(pt, &p.hole) {
-                inside.push(pt);
-            }
-        }
-    }
+    let triangulation = Triangulation::new(&p.hole);
 
-    let mut rng = rand::thread_rng();
-
-    let deltass: Vec<Vec<Pt>> = checker.edge_ranges.iter()
-        .map(|&(min_d, max_d, _)| deltas(min_d, max_d))
-        .collect();
+    let mut rng = StdRng::seed_from_u64(seed);
 
     'outer: loop {
+        if stop.should_stop() {
+            return;
+        }
 
         // eprintln!("------");
         let mut pts: Vec<Option<Pt>> = vec![None; p.figure.vertices.len()];
@@ -96,7 +102,7 @@ fn rail() {
                 None => {
                     v_idx = rng.gen_range(0..pts.len());
                     if rng.gen() {
-                        pt = inside[rng.gen_range(0..inside.len())];
+                        pt = triangulation.random_point(&mut rng);
                     } else {
                         pt = p.hole[rng.gen_range(0..p.hole.len())];
                     }
@@ -114,20 +120,11 @@ fn rail() {
                 let (min_d, max_d, _) = checker.edge_ranges[e_idx];
                 let placement = &mut placements[v2_idx];
                 if placement.is_empty() {
-                    if Some(e_idx) == superflex_e_idx {
-                        for &pt2 in &inside {
-                            if checker.edge_in_hole(pt, pt2) {
-                                placement.push(pt2);
-                            }
-                        }
+                    *placement = if Some(e_idx) == superflex_e_idx {
+                        visible_from(&checker.hole_index, pt, checker.hole_index.bbox)
                     } else {
-                        for &delta in &deltass[e_idx] {
-                            let pt2 = pt + delta;
-                            if checker.edge_in_hole(pt, pt2) {
-                                placement.push(pt2);
-                            }
-                        }
-                    }
+                        candidates(&checker.hole_index, &checker.inside, &[(pt, min_d, max_d)])
+                    }.as_points();
                 } else {
                     placement.retain(|&pt2| {
                         let d = pt.dist2(pt2);
@@ -158,7 +155,9 @@ fn rail() {
                     vertices: pts.iter().map(|pt| pt.unwrap()).collect(),
                     bonuses: bonuses.clone(),
                 };
-                submitter.update(&p, &pose);
+                if !on_pose(&pose) {
+                    return;
+                }
                 continue 'outer;
             }
         }
@@ -1,33 +1,49 @@
-use rand::Rng;
+use rand::prelude::*;
 //use rand::seq::SliceRandom;
 use crate::prelude::*;
 use crate::checker::Checker;
 use crate::submitter::Submitter;
-
-fn deltas(min_d: i64, max_d: i64) -> Vec<Pt> {
-    let mut result = vec![];
-    for x in -max_d..=max_d {
-        for y in -max_d..=max_d {
-            let d = Pt { x, y }.len2();
-            if min_d <= d && d <= max_d {
-                result.push(Pt {x, y});
-            }
-        }
-    }
-    result
-}
+use crate::visibility::corner_visibility;
+use crate::candidates::candidates;
+use crate::rle::Set2D;
+use crate::solver::{best_found, Solver, SolverError};
+use crate::cancel::CancelToken;
+use crate::progress::Progress;
 
 crate::entry_point!("rail_constrained", rail_constrained);
 fn rail_constrained() {
     let problem_id: i32 = std::env::args().nth(2).unwrap().parse().unwrap();
 
-    let mut submitter = Submitter::new(problem_id, "rail_constrained".to_string());
+    let seed = crate::util::seed_flag();
+
+    let mut submitter = Submitter::new(problem_id, format!("rail_constrained seed={}", seed));
 
     
     let p = load_problem(problem_id);
+    rail_constrained_search(&p, seed, &CancelToken::default(), &mut |pose| {
+        submitter.update(&p, pose);
+        true
+    });
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RailConstrainedSolver {
+    pub seed: Option<u64>,
+}
+
+impl Solver for RailConstrainedSolver {
+    fn solve(&mut self, problem: &Problem, _start: Option<&Pose>, stop: &CancelToken, progress: &Progress) -> Result<Pose, SolverError> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        best_found(problem, stop, progress, |on_pose| rail_constrained_search(problem, seed, stop, on_pose))
+    }
+}
+
+/// Like rail_search(), but fills the hole corners first.
+pub fn rail_constrained_search(p: &Problem, seed: u64, stop: &CancelToken, on_pose: &mut dyn FnMut(&Pose) -> bool) {
     let bonuses = vec![];
 
-    let mut checker = Checker::new(&p, &bonuses, p.figure.vertices.len());
+    let mut checker = Checker::new(p, &bonuses, p.figure.vertices.len());
     let edges = checker.edges.clone();
     let mut inci: Vec<Vec<usize>> = vec![vec![]; edges.len()];
     for (i, &(start, end)) in edges.iter().enumerate() {
@@ -35,14 +51,14 @@ fn rail_constrained() {
         inci[end].push(i);
     }
 
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(seed);
 
-    //eprintln!("deltass");
-    let deltass: Vec<Vec<Pt>> = checker.edge_ranges.iter()
-        .map(|&(min_d, max_d, _)| deltas(min_d, max_d))
-        .collect();
+    let visibility = corner_visibility(&checker.hole_index);
 
     'outer: loop {
+        if stop.should_stop() {
+            return;
+        }
         //eprintln!("------");
         let mut pts: Vec<Option<Pt>> = vec![None; p.figure.vertices.len()];
         let mut placements: Vec<Vec<Pt>> = vec![vec![]; p.figure.vertices.len()];
@@ -106,6 +122,7 @@ fn rail_constrained() {
             }
 
             pts[v_idx] = Some(pt);
+            let corner = p.hole.iter().position(|&h| h == pt);
 
             // Update placement constraints for each unplaced neighbor.
             for &e_idx in &inci[v_idx] {
@@ -117,16 +134,17 @@ fn rail_constrained() {
                 let (min_d, max_d, _) = checker.edge_ranges[e_idx];
                 let placement = &mut placements[v2_idx];
                 if placement.is_empty() {
-                    for &delta in &deltass[e_idx] {
-                        let pt2 = pt + delta;
-                        if checker.edge_in_hole(pt, pt2) {
-                            placement.push(pt2);
-                        }
-                    }
+                    *placement = match corner {
+                        Some(k) => visibility[k].intersection(&Set2D::ring(pt.x, pt.y, min_d, max_d)),
+                        None => candidates(&checker.hole_index, &checker.inside, &[(pt, min_d, max_d)]),
+                    }.as_points();
                 } else {
                     placement.retain(|&pt2| {
                         let d = pt.dist2(pt2);
-                        min_d <= d && d <= max_d && checker.edge_in_hole(pt, pt2)
+                        min_d <= d && d <= max_d && match corner {
+                            Some(k) => visibility[k].contains(pt2),
+                            None => checker.edge_in_hole(pt, pt2),
+                        }
                     });
                 }
 
@@ -145,7 +163,9 @@ fn rail_constrained() {
                     vertices: pts.iter().map(|pt| pt.unwrap()).collect(),
                     bonuses: bonuses.clone(),
                 };
-                submitter.update(&p, &pose);
+                if !on_pose(&pose) {
+                    return;
+                }
                 continue 'outer;
             }
         }
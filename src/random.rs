@@ -2,39 +2,40 @@ use rand::prelude::*;
 use crate::prelude::*;
 use crate::geom::segment_in_poly;
 use crate::util::{load_problem, store_solution};
+use crate::checker::get_dislikes;
 use crate::solver::{Solver, SolverError};
-use core::time::Duration;
 use std::time::Instant;
+use crate::cancel::CancelToken;
+use crate::progress::Progress;
 
-pub struct RandomSolver;
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RandomSolver {
+    pub seed: Option<u64>,
+}
 
 impl Solver for RandomSolver {
-    fn solve(&mut self, problem: &Problem, duration_limit: Option<Duration>) -> Result<Pose, SolverError> {
+    fn solve(&mut self, problem: &Problem, _start: Option<&Pose>, stop: &CancelToken, progress: &Progress) -> Result<Pose, SolverError> {
         let start_time = Instant::now();
-        dbg!(duration_limit);
-        let mut rng = StdRng::seed_from_u64(42);
+        let mut rng = StdRng::seed_from_u64(self.seed.unwrap_or_else(rand::random));
 
-        dbg!(problem.figure.vertices.len());
         let vertices = &problem.figure.vertices;
         let x1 = vertices.iter().map(|pt| pt.x).min().unwrap();
         let y1 = vertices.iter().map(|pt| pt.y).min().unwrap();
         let x2 = vertices.iter().map(|pt| pt.x).max().unwrap();
         let y2 = vertices.iter().map(|pt| pt.y).max().unwrap();
 
-        dbg!((x1, y1, x2, y2));
-        dbg!(problem.epsilon);
         let mut cnt = 0;
         let solution;
         loop {
-            if let Some(duration_limit) = duration_limit {
-                if start_time.elapsed() > duration_limit {
-                    return Err(SolverError::SolverOutOfTime(start_time.elapsed()));
-                }
+            if cnt % 1000 == 0 && stop.should_stop() {
+                return Err(SolverError::stopped(stop, start_time));
             }
-            cnt += 1;
-            if cnt % 10000000 == 0 {
-                dbg!(cnt);
+            if cnt % 1000000 == 0 {
+                // no score, nothing's valid until the end
+                progress.report(cnt, None, None, None);
             }
+            cnt += 1;
 
             let pose: Vec<Pt> = vertices.iter()
                 .map(|_| Pt::new(rng.gen_range(x1..=x2), rng.gen_range(y1..=y2)))
@@ -61,8 +62,7 @@ impl Solver for RandomSolver {
                 continue;
             }
 
-            dbg!(cnt);
-            dbg!(&pose);
+            progress.report(cnt, None, None, Some(0));
             solution = Some(Pose { vertices: pose, bonuses: vec![] });
             break;
         }
@@ -82,7 +82,10 @@ fn random_solver() {
     };
 
     let problem: Problem = load_problem(problem_no);
-    let mut solver = RandomSolver;
-    let pose = solver.solve(&problem, None).ok().unwrap();
+    let seed = crate::util::seed_flag();
+    let mut solver = RandomSolver { seed: Some(seed) };
+    let progress = Progress::new("random").with_sink(crate::progress::StderrSink::default());
+    let pose = solver.solve(&problem, None, &CancelToken::default(), &progress).ok().unwrap();
     store_solution(problem_no, &pose);
+    crate::solver::store_run(problem_no, "random", &serde_json::json!({"seed": seed}), get_dislikes(&problem, &pose.vertices));
 }
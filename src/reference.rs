@@ -0,0 +1,321 @@
+// Slow but straightforward pose checker to test checker.rs against.
+//
+// A segment is cut at every point where it meets the hole boundary,
+// proper crossings and collinear overlaps included. Between two
+// consecutive cuts it doesn't touch the boundary, so it's entirely inside
+// or entirely outside, and its midpoint tells which. All the points
+// are exact rationals, no orientation shortcuts and no grid.
+
+use rand::prelude::*;
+use crate::prelude::*;
+use crate::checker::check_pose;
+
+// x / den, y / den with den > 0
+#[derive(Clone, Copy, Debug)]
+struct RatPt {
+    x: i128,
+    y: i128,
+    den: i128,
+}
+
+impl RatPt {
+    fn from_pt(p: Pt) -> RatPt {
+        RatPt { x: p.x as i128, y: p.y as i128, den: 1 }
+    }
+}
+
+// Boundary included. Even-odd rule with a ray going right,
+// an edge counts if it spans [min_y, max_y) of the ray's height.
+fn rat_pt_in_poly(pt: RatPt, poly: &[Pt]) -> bool {
+    let RatPt { x, y, den } = pt;
+    let mut inside = false;
+    for i in 0..poly.len() {
+        let p = poly[i];
+        let q = poly[(i + 1) % poly.len()];
+        let (px, py, qx, qy) = (p.x as i128, p.y as i128, q.x as i128, q.y as i128);
+
+        let on_line = (qx - px) * (y - py * den) == (qy - py) * (x - px * den);
+        if on_line &&
+           px.min(qx) * den <= x && x <= px.max(qx) * den &&
+           py.min(qy) * den <= y && y <= py.max(qy) * den {
+            return true;
+        }
+
+        if py == qy || y < py.min(qy) * den || y >= py.max(qy) * den {
+            continue;
+        }
+        // x of the edge at height y / den, times den * (qy - py)
+        let mut edge_x = px * den * (qy - py) + (y - py * den) * (qx - px);
+        let mut pt_x = x * (qy - py);
+        if qy < py {
+            edge_x = -edge_x;
+            pt_x = -pt_x;
+        }
+        if edge_x > pt_x {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// Parameters t in [0, 1] (as num / den, den > 0) of the points of a + t * (b - a)
+// that are also on the segment (p, q). For collinear overlaps, the ends of the overlap.
+fn meeting_params((a, b): (Pt, Pt), (p, q): (Pt, Pt)) -> Vec<(i128, i128)> {
+    let cross = |u: Pt, v: Pt| u.x as i128 * v.y as i128 - u.y as i128 * v.x as i128;
+    let dot = |u: Pt, v: Pt| u.x as i128 * v.x as i128 + u.y as i128 * v.y as i128;
+    let r = b - a;
+    let s = q - p;
+    let in_unit = |num: i128, den: i128| 0 <= num && num <= den;
+    let mut res = vec![];
+    let mut denom = cross(r, s);
+    if denom != 0 {
+        let mut t = cross(p - a, s);
+        let mut u = cross(p - a, r);
+        if denom < 0 {
+            denom = -denom;
+            t = -t;
+            u = -u;
+        }
+        if in_unit(t, denom) && in_unit(u, denom) {
+            res.push((t, denom));
+        }
+    } else if cross(p - a, r) == 0 {
+        // collinear: ends of either segment that lie on the other one
+        let rr = dot(r, r);
+        for e in [p, q] {
+            let t = dot(e - a, r);
+            if in_unit(t, rr) {
+                res.push((t, rr));
+            }
+        }
+        let ss = dot(s, s);
+        for (e, t) in [(a, (0, 1)), (b, (1, 1))] {
+            if ss > 0 && in_unit(dot(e - p, s), ss) && cross(e - p, s) == 0 {
+                res.push(t);
+            }
+        }
+    }
+    res
+}
+
+fn rat_cmp(a: (i128, i128), b: (i128, i128)) -> std::cmp::Ordering {
+    (a.0 * b.1).cmp(&(b.0 * a.1))
+}
+
+/// Same contract as geom::segment_in_poly: boundary included.
+pub fn segment_in_hole_reference((a, b): (Pt, Pt), hole: &[Pt]) -> bool {
+    if !rat_pt_in_poly(RatPt::from_pt(a), hole) || !rat_pt_in_poly(RatPt::from_pt(b), hole) {
+        return false;
+    }
+    if a == b {
+        return true;
+    }
+    let mut ts = vec![(0, 1), (1, 1)];
+    for i in 0..hole.len() {
+        ts.extend(meeting_params((a, b), (hole[i], hole[(i + 1) % hole.len()])));
+    }
+    ts.sort_by(|&t1, &t2| rat_cmp(t1, t2));
+    ts.dedup_by(|t1, t2| rat_cmp(*t1, *t2) == std::cmp::Ordering::Equal);
+
+    let r = b - a;
+    let at = |num: i128, den: i128| RatPt {
+        x: a.x as i128 * den + num * r.x as i128,
+        y: a.y as i128 * den + num * r.y as i128,
+        den,
+    };
+    ts.iter().all(|&(num, den)| rat_pt_in_poly(at(num, den), hole)) &&
+    ts.windows(2).all(|w| {
+        let ((n1, d1), (n2, d2)) = (w[0], w[1]);
+        rat_pt_in_poly(at(n1 * d2 + n2 * d1, 2 * d1 * d2), hole)
+    })
+}
+
+pub struct ReferenceVerdict {
+    pub fits_in_hole: Vec<bool>,
+    pub length_ok: Vec<bool>,
+    pub dislikes: i64,
+    pub valid: bool,
+}
+
+/// Plain poses only, bonuses are ignored.
+/// Vertices must be within a few thousand of the hole, or the rationals overflow.
+pub fn check_pose_reference(problem: &Problem, pose: &Pose) -> ReferenceVerdict {
+    let vs = &pose.vertices;
+    assert_eq!(vs.len(), problem.figure.vertices.len());
+    let mut fits_in_hole = vec![];
+    let mut length_ok = vec![];
+    for &(i, j) in &problem.figure.edges {
+        fits_in_hole.push(segment_in_hole_reference((vs[i], vs[j]), &problem.hole));
+        // |actual / original - 1| <= epsilon / EPS_BASE
+        let d = problem.figure.vertices[i].dist2(problem.figure.vertices[j]) as i128;
+        let actual = vs[i].dist2(vs[j]) as i128;
+        length_ok.push((actual - d).abs() * EPS_BASE as i128 <= problem.epsilon as i128 * d);
+    }
+    let dislikes = problem.hole.iter()
+        .map(|&h| vs.iter().map(|&v| v.dist2(h)).min().unwrap())
+        .sum();
+    let valid = fits_in_hole.iter().chain(&length_ok).all(|&ok| ok);
+    ReferenceVerdict { fits_in_hole, length_ok, dislikes, valid }
+}
+
+// No two edges meet except for neighbours at their common vertex.
+fn is_simple(poly: &[Pt]) -> bool {
+    let n = poly.len();
+    let edge = |i: usize| (poly[i], poly[(i + 1) % n]);
+    for i in 0..n {
+        if poly[i] == poly[(i + 1) % n] {
+            return false;
+        }
+        for j in i + 1..n {
+            let expected = if j == i + 1 || i == 0 && j == n - 1 { 1 } else { 0 };
+            let mut ts = meeting_params(edge(i), edge(j));
+            ts.sort_by(|&t1, &t2| rat_cmp(t1, t2));
+            ts.dedup_by(|t1, t2| rat_cmp(*t1, *t2) == std::cmp::Ordering::Equal);
+            if ts.len() != expected {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn fits_by_checker(seg: (Pt, Pt), hole: &[Pt]) -> bool {
+    let problem = Problem {
+        bonuses: vec![],
+        hole: hole.to_vec(),
+        figure: crate::domain_model::Figure { vertices: vec![seg.0, seg.1], edges: vec![(0, 1)] },
+        epsilon: 0,
+    };
+    let pose = Pose { vertices: vec![seg.0, seg.1], bonuses: vec![] };
+    check_pose(&problem, &pose).edge_statuses[0].fits_in_hole
+}
+
+// Drops hole vertices while the checkers still disagree about the segment.
+fn minimize_hole(seg: (Pt, Pt), hole: &[Pt]) -> Vec<Pt> {
+    let disagree = |hole: &[Pt]| fits_by_checker(seg, hole) != segment_in_hole_reference(seg, hole);
+    let mut hole = hole.to_vec();
+    let mut k = 0;
+    while k < hole.len() && hole.len() > 3 {
+        let mut smaller = hole.clone();
+        smaller.remove(k);
+        if is_simple(&smaller) && disagree(&smaller) {
+            hole = smaller;
+        } else {
+            k += 1;
+        }
+    }
+    hole
+}
+
+// Lattice point likely to be degenerate: a hole vertex, a point on a hole edge,
+// or anything around the hole.
+fn random_vertex(hole: &[Pt], rng: &mut StdRng) -> Pt {
+    let b = crate::geom::BBox::from_pts(hole);
+    match rng.gen_range(0..10) {
+        0..=3 => hole[rng.gen_range(0..hole.len())],
+        4..=5 => {
+            let k = rng.gen_range(0..hole.len());
+            let (p, q) = (hole[k], hole[(k + 1) % hole.len()]);
+            let d = q - p;
+            let g = crate::geom::gcd(d.x, d.y).max(1);
+            let i = rng.gen_range(0..=g);
+            Pt::new(p.x + d.x / g * i, p.y + d.y / g * i)
+        }
+        _ => Pt::new(rng.gen_range(b.min_x - 2..=b.max_x + 2), rng.gen_range(b.min_y - 2..=b.max_y + 2)),
+    }
+}
+
+// Compares check_pose() with check_pose_reference(), bonuses stripped.
+// Returns false after reporting the first mismatch.
+fn compare(problem_id: i32, problem: &Problem, vertices: Vec<Pt>) -> bool {
+    let pose = Pose { vertices, bonuses: vec![] };
+    let check = check_pose(problem, &pose);
+    let reference = check_pose_reference(problem, &pose);
+    for (i, &(v1, v2)) in problem.figure.edges.iter().enumerate() {
+        let es = &check.edge_statuses[i];
+        let seg = (pose.vertices[v1], pose.vertices[v2]);
+        if es.fits_in_hole != reference.fits_in_hole[i] {
+            let hole = minimize_hole(seg, &problem.hole);
+            eprintln!("problem {}, edge {}: segment_in_hole differs", problem_id, i);
+            let repro = serde_json::json!({
+                "hole": hole,
+                "segment": [seg.0, seg.1],
+                "check_pose": fits_by_checker(seg, &hole),
+                "reference": segment_in_hole_reference(seg, &hole),
+            });
+            let path = "outputs/differential_repro.json";
+            std::fs::write(project_path(path), serde_json::to_vec(&repro).unwrap()).unwrap();
+            eprintln!("{}", repro);
+            eprintln!("saved to {}", path);
+            return false;
+        }
+        let length_ok = es.min_length <= es.actual_length && es.actual_length <= es.max_length;
+        if length_ok != reference.length_ok[i] {
+            let d = problem.figure.vertices[v1].dist2(problem.figure.vertices[v2]);
+            eprintln!("problem {}, edge {}: length check differs, original {}, actual {}, epsilon {}: {} vs {}",
+                      problem_id, i, d, es.actual_length, problem.epsilon, length_ok, reference.length_ok[i]);
+            return false;
+        }
+    }
+    if check.dislikes != reference.dislikes || check.valid != reference.valid {
+        eprintln!("problem {}: dislikes {} vs {}, valid {} vs {}, pose {}",
+                  problem_id, check.dislikes, reference.dislikes, check.valid, reference.valid,
+                  serde_json::to_string(&pose).unwrap());
+        return false;
+    }
+    true
+}
+
+// Random poses and whatever solutions are in outputs/.
+pub fn differential(seed: u64, poses_per_problem: usize) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut checked = 0;
+    for problem_id in crate::registry::selected_problem_ids() {
+        let problem = load_problem(problem_id);
+        let n = problem.figure.vertices.len();
+        let mut poses = vec![problem.figure.vertices.clone()];
+        if let Ok(data) = std::fs::read(project_path(format!("outputs/sol_{}.json", problem_id))) {
+            if let Ok(pose) = serde_json::from_slice::<Pose>(&data) {
+                if pose.vertices.len() == n {
+                    poses.push(pose.vertices);
+                }
+            }
+        }
+        for _ in 0..poses_per_problem {
+            poses.push((0..n).map(|_| random_vertex(&problem.hole, &mut rng)).collect());
+        }
+        for vertices in poses {
+            if !compare(problem_id, &problem, vertices) {
+                return;
+            }
+            checked += 1;
+        }
+    }
+    eprintln!("{} poses checked, no mismatches", checked);
+}
+
+#[cfg(test)]
+#[test]
+fn test_reference_agrees() {
+    use crate::geom::segment_in_poly;
+    let mut rng = StdRng::seed_from_u64(42);
+    let comb = vec![
+        Pt::new(0, 0), Pt::new(10, 0), Pt::new(10, 5), Pt::new(8, 3),
+        Pt::new(6, 5), Pt::new(4, 3), Pt::new(2, 5), Pt::new(0, 5),
+    ];
+    for hole in [comb, load_problem(1).hole, load_problem(31).hole] {
+        assert!(is_simple(&hole));
+        for _ in 0..3000 {
+            let seg = (random_vertex(&hole, &mut rng), random_vertex(&hole, &mut rng));
+            assert_eq!(segment_in_hole_reference(seg, &hole), segment_in_poly(seg, &hole), "{:?}", seg);
+        }
+    }
+    assert!(!is_simple(&[Pt::new(0, 0), Pt::new(2, 2), Pt::new(2, 0), Pt::new(0, 2)]));
+    assert!(!is_simple(&[Pt::new(0, 0), Pt::new(4, 0), Pt::new(2, 0), Pt::new(2, 2)]));
+
+    for problem_id in [1, 9] {
+        let p = load_problem(problem_id);
+        let pose = Pose { vertices: p.figure.vertices.clone(), bonuses: vec![] };
+        assert!(compare(problem_id, &p, pose.vertices));
+    }
+}
@@ -0,0 +1,229 @@
+// Problems found on disk: data/problems/*.problem plus whatever is in
+// the directories listed in EXTRA_PROBLEM_DIRS (separated like PATH).
+// File names must be problem ids, extra problems must not reuse official ids.
+//
+// Entry points that go over many problems take the same filter:
+//   --problems 1-20,45,bonus:GLOBALIST,size:small
+// selects the union of the listed ids, ranges and tags.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeClass {
+    Small,   // up to 20 figure vertices
+    Medium,  // up to 60
+    Large,
+}
+
+impl SizeClass {
+    fn of(problem: &Problem) -> SizeClass {
+        match problem.figure.vertices.len() {
+            0..=20 => SizeClass::Small,
+            21..=60 => SizeClass::Medium,
+            _ => SizeClass::Large,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SizeClass::Small => "small",
+            SizeClass::Medium => "medium",
+            SizeClass::Large => "large",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProblemEntry {
+    pub id: i32,
+    pub path: PathBuf,
+    pub official: bool,  // from data/problems, known to the server
+    pub weight: f64,
+    pub size: SizeClass,
+    // "bonus:<name>" for every bonus the problem offers, "size:<class>",
+    // "eps:0" for rigid figures, "extra" for unofficial problems
+    pub tags: Vec<String>,
+}
+
+pub struct Registry {
+    pub entries: Vec<ProblemEntry>,  // sorted by id
+}
+
+impl Registry {
+    fn discover() -> Registry {
+        let extra_dirs: Vec<PathBuf> = std::env::var_os("EXTRA_PROBLEM_DIRS")
+            .map(|dirs| std::env::split_paths(&dirs).collect())
+            .unwrap_or_default();
+        Registry::from_dirs(&extra_dirs)
+    }
+
+    // A broken or duplicate file is skipped with a warning, one bad file
+    // in someone's extra dir shouldn't take down every tool.
+    fn from_dirs(extra_dirs: &[PathBuf]) -> Registry {
+        let mut entries = vec![];
+        scan_dir(&project_path("data/problems"), true, &mut entries);
+        for dir in extra_dirs {
+            scan_dir(dir, false, &mut entries);
+        }
+        // stable, so the first one found wins, official ones are scanned first
+        entries.sort_by_key(|e| e.id);
+        entries.dedup_by(|dup, first| {
+            if dup.id != first.id {
+                return false;
+            }
+            eprintln!("skipping {:?}: problem {} is already in {:?}", dup.path, dup.id, first.path);
+            true
+        });
+        Registry { entries }
+    }
+
+    pub fn get(&self, problem_id: i32) -> Option<&ProblemEntry> {
+        self.entries.binary_search_by_key(&problem_id, |e| e.id).ok().map(|i| &self.entries[i])
+    }
+
+    /// Ids of the entries matching the filter, see the module comment.
+    pub fn select(&self, filter: &str) -> Result<Vec<i32>, String> {
+        let mut selected = vec![false; self.entries.len()];
+        for item in filter.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let range = match item.split_once('-') {
+                Some((a, b)) => a.parse::<i32>().ok().zip(b.parse::<i32>().ok()),
+                None => item.parse::<i32>().ok().map(|id| (id, id)),
+            };
+            let mut matched = false;
+            for (e, sel) in self.entries.iter().zip(&mut selected) {
+                let hit = match range {
+                    Some((a, b)) => a <= e.id && e.id <= b,
+                    None => e.tags.iter().any(|t| t.eq_ignore_ascii_case(item)),
+                };
+                if hit {
+                    *sel = true;
+                    matched = true;
+                }
+            }
+            if !matched {
+                return Err(format!("no problems match {:?}", item));
+            }
+        }
+        Ok(self.entries.iter().zip(selected).filter(|&(_, sel)| sel).map(|(e, _)| e.id).collect())
+    }
+}
+
+fn scan_dir(dir: &Path, official: bool, entries: &mut Vec<ProblemEntry>) {
+    let files = match std::fs::read_dir(dir) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("can't read problems from {:?}: {}", dir, e);
+            return;
+        }
+    };
+    for file in files {
+        let path = match file {
+            Ok(file) => file.path(),
+            Err(e) => {
+                eprintln!("can't read problems from {:?}: {}", dir, e);
+                continue;
+            }
+        };
+        if path.extension().is_none_or(|ext| ext != "problem") {
+            continue;
+        }
+        let id = match path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok()) {
+            Some(id) => id,
+            None => {
+                eprintln!("skipping {:?}: file name is not a problem id", path);
+                continue;
+            }
+        };
+        let problem: Problem = match std::fs::read(&path).map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string())) {
+            Ok(problem) => problem,
+            Err(e) => {
+                eprintln!("skipping {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let size = SizeClass::of(&problem);
+        let mut tags = vec![format!("size:{}", size.name())];
+        for b in &problem.bonuses {
+            let tag = format!("bonus:{:?}", b.bonus);
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        if problem.epsilon == 0 {
+            tags.push("eps:0".to_string());
+        }
+        if !official {
+            tags.push("extra".to_string());
+        }
+        entries.push(ProblemEntry { id, path, official, weight: problem.weight(), size, tags });
+    }
+}
+
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::discover)
+}
+
+/// Problems picked by --problems on the command line, all of them by default.
+pub fn selected_problem_ids() -> Vec<i32> {
+    match crate::util::flag_value("problems") {
+        None => registry().entries.iter().map(|e| e.id).collect(),
+        Some(filter) => registry().select(&filter).unwrap_or_else(|e| {
+            eprintln!("--problems {}: {}", filter, e);
+            std::process::exit(1);
+        }),
+    }
+}
+
+crate::entry_point!("problems", list_problems);
+fn list_problems() {
+    for id in selected_problem_ids() {
+        let e = registry().get(id).unwrap();
+        println!("{:>4} {:>7.0} {:<6} {}  {}", e.id, e.weight, e.size.name(), e.tags.join(" "), e.path.display());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_registry() {
+    let r = registry();
+    let official: Vec<i32> = r.entries.iter().filter(|e| e.official).map(|e| e.id).collect();
+    assert_eq!(official, (1..=132).collect::<Vec<i32>>());
+    assert_eq!(r.get(11).unwrap().size, SizeClass::Small);
+    assert!(r.get(0).is_none());
+
+    assert_eq!(r.select("1-3,7, 5").unwrap(), vec![1, 2, 3, 5, 7]);
+    assert!(r.select("").unwrap().is_empty());
+    assert!(r.select("bonus:nope").is_err());
+    let globalist = r.select("bonus:GLOBALIST").unwrap();
+    assert!(!globalist.is_empty());
+    for id in globalist {
+        assert!(load_problem(id).bonuses.iter().any(|b| b.bonus == crate::domain_model::BonusName::GLOBALIST));
+    }
+    let small = r.select("size:small").unwrap();
+    let large = r.select("size:large").unwrap();
+    assert!(small.iter().all(|&id| load_problem(id).figure.vertices.len() <= 20));
+    assert!(large.iter().all(|&id| load_problem(id).figure.vertices.len() > 60));
+    assert_eq!(small.len() + r.select("size:medium").unwrap().len() + large.len(), r.entries.len());
+}
+
+#[cfg(test)]
+#[test]
+fn test_registry_skips_bad_files() {
+    let dir = project_path("outputs/test_registry");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(project_path("data/problems/11.problem"), dir.join("1.problem")).unwrap();
+    std::fs::copy(project_path("data/problems/11.problem"), dir.join("1000.problem")).unwrap();
+    std::fs::write(dir.join("1001.problem"), "{").unwrap();
+    std::fs::write(dir.join("nope.problem"), "{}").unwrap();
+
+    let r = Registry::from_dirs(&[dir.clone(), project_path("outputs/test_registry_missing")]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(r.get(1).unwrap().official);
+    assert!(!r.get(1000).unwrap().official);
+    assert!(r.get(1001).is_none());
+    assert_eq!(r.entries.len(), 133);
+}
@@ -0,0 +1,154 @@
+// SVG pictures of problems and poses, for reports and logs.
+// Colors are the same as in the visualizer.
+
+use std::fmt::Write;
+use crate::prelude::*;
+use crate::checker::check_pose;
+use crate::domain_model::BonusName;
+use crate::geom::{BBox, MAX_COORD};
+
+const CLR_HOLE: &str = "#777777";
+const CLR_HOLE_FILL: &str = "#F4F4F4";
+const CLR_OK_EDGE: &str = "#007F0E";
+const CLR_SHORT_EDGE: &str = "#B200FF";
+const CLR_LONG_EDGE: &str = "#D10000";
+const CLR_VERTEX: &str = "#222222";
+const CLR_FIGURE: &str = "#BBBBBB";
+
+fn bonus_color(b: BonusName) -> &'static str {
+    match b {
+        BonusName::GLOBALIST => "#FFFF00",
+        BonusName::BREAK_A_LEG => "#5555FF",
+        BonusName::WALLHACK => "#FFA500",
+        BonusName::SUPERFLEX => "#00FFFF",
+    }
+}
+
+/// The hole, bonus positions and the original figure,
+/// and if there is a pose, its edges colored by check_pose() verdicts:
+/// dashed if outside the hole, red if too long, purple if too short.
+pub fn render_svg(problem: &Problem, pose: Option<&Pose>, caption: &str) -> String {
+    let in_range = |p: &Pt| p.x.unsigned_abs() <= MAX_COORD as u64 && p.y.unsigned_abs() <= MAX_COORD as u64;
+    let mut pts = problem.hole.clone();
+    pts.extend(&problem.figure.vertices);
+    if let Some(pose) = pose {
+        pts.extend(pose.vertices.iter().filter(|p| in_range(p)));
+    }
+    let b = if pts.is_empty() { BBox { min_x: 0, max_x: 1, min_y: 0, max_y: 1 } } else { BBox::from_pts(&pts) };
+    let size = (b.max_x - b.min_x).max(b.max_y - b.min_y).max(1) as f64;
+    // one pixel, roughly, when the picture is 600 pixels wide
+    let px = size / 600.0;
+    let margin = 10.0 * px;
+    let (x0, y0) = (b.min_x as f64 - margin, b.min_y as f64 - margin);
+    let (w, h) = ((b.max_x - b.min_x) as f64 + 2.0 * margin, (b.max_y - b.min_y) as f64 + 2.0 * margin + 20.0 * px);
+
+    let mut s = String::new();
+    writeln!(s, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{:.0}" height="{:.0}">"#,
+             x0, y0, w, h, w / px, h / px).unwrap();
+    writeln!(s, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="white"/>"#, x0, y0, w, h).unwrap();
+
+    let points: Vec<String> = problem.hole.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    writeln!(s, r#"<polygon points="{}" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+             points.join(" "), CLR_HOLE_FILL, CLR_HOLE, 2.0 * px).unwrap();
+
+    for bonus in &problem.bonuses {
+        writeln!(s, r#"<circle cx="{}" cy="{}" r="{}" fill="{}" fill-opacity="0.7"><title>{:?} for {}</title></circle>"#,
+                 bonus.position.x, bonus.position.y, 8.0 * px, bonus_color(bonus.bonus),
+                 bonus.bonus, bonus.problem).unwrap();
+    }
+
+    let fig = &problem.figure;
+    for &(i, j) in &fig.edges {
+        if let (Some(p), Some(q)) = (fig.vertices.get(i), fig.vertices.get(j)) {
+            let width = if pose.is_some() { px } else { 2.0 * px };
+            let color = if pose.is_some() { CLR_FIGURE } else { CLR_OK_EDGE };
+            writeln!(s, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"/>"#,
+                     p.x, p.y, q.x, q.y, color, width).unwrap();
+        }
+    }
+
+    if let Some(pose) = pose {
+        let check = check_pose(problem, pose);
+        let vs = &pose.vertices;
+        for (&(i, j), es) in check.edges.iter().zip(&check.edge_statuses) {
+            let (p, q) = match (vs.get(i), vs.get(j)) {
+                (Some(p), Some(q)) if in_range(p) && in_range(q) => (p, q),
+                _ => continue,
+            };
+            let color = if es.actual_length > es.max_length {
+                CLR_LONG_EDGE
+            } else if es.actual_length < es.min_length {
+                CLR_SHORT_EDGE
+            } else {
+                CLR_OK_EDGE
+            };
+            let dash = if es.fits_in_hole { String::new() } else { format!(r#" stroke-dasharray="{} {}""#, 4.0 * px, 4.0 * px) };
+            writeln!(s, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"{}><title>{}-{}: {} ({} : {})</title></line>"#,
+                     p.x, p.y, q.x, q.y, color, 2.0 * px, dash,
+                     i, j, es.actual_length, es.min_length, es.max_length).unwrap();
+        }
+        for (i, p) in vs.iter().enumerate().filter(|(_, p)| in_range(p)) {
+            writeln!(s, r#"<circle cx="{}" cy="{}" r="{}" fill="{}"><title>{}: {:?}</title></circle>"#,
+                     p.x, p.y, 2.5 * px, CLR_VERTEX, i, p).unwrap();
+        }
+        let verdict = if check.valid {
+            "valid".to_string()
+        } else {
+            format!("not valid: {}", check.violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", "))
+        };
+        writeln!(s, r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif">{} {} dislikes, {}</text>"#,
+                 x0 + margin, y0 + h - 5.0 * px, 14.0 * px, escape(caption), check.dislikes, escape(&verdict)).unwrap();
+    } else {
+        writeln!(s, r#"<text x="{}" y="{}" font-size="{}" font-family="sans-serif">{}</text>"#,
+                 x0 + margin, y0 + h - 5.0 * px, 14.0 * px, escape(caption)).unwrap();
+    }
+    s.push_str("</svg>\n");
+    s
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+crate::entry_point!("render", render);
+fn render() {
+    let usage = "usage: cargo run render <problem id or file> [pose.json]";
+    let arg = std::env::args().nth(2).unwrap_or_else(|| {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    });
+    let (problem, name) = match arg.parse::<i32>() {
+        Ok(problem_id) => (crate::util::try_load_problem(problem_id), problem_id.to_string()),
+        Err(_) => {
+            let stem = std::path::Path::new(&arg).file_stem().unwrap().to_string_lossy().to_string();
+            (crate::util::try_load_problem_file(&arg), stem)
+        }
+    };
+    let problem = problem.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let pose: Option<Pose> = std::env::args().nth(3).map(|path| {
+        let data = std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        serde_json::from_slice(&data).unwrap()
+    });
+    let svg = render_svg(&problem, pose.as_ref(), &format!("problem {}", name));
+    let path = format!("outputs/render_{}.svg", name);
+    std::fs::write(project_path(&path), svg).unwrap();
+    println!("see {}", path);
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_svg() {
+    let p = load_problem(1);
+    let svg = render_svg(&p, None, "problem 1");
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<line").count(), p.figure.edges.len());
+
+    let mut vertices = p.figure.vertices.clone();
+    vertices[0] = Pt::new(i64::MAX, 0);
+    let svg = render_svg(&p, Some(&Pose { vertices, bonuses: vec![] }), "<pose>");
+    assert!(svg.contains("&lt;pose&gt;") && svg.contains("not valid"));
+    assert!(svg.contains("stroke-dasharray"));
+}
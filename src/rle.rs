@@ -1,10 +1,10 @@
-#![allow(unused_imports)]
-#![allow(dead_code)]
+// Run-length encoded sets of lattice points.
+// Solvers use them to keep candidate positions of vertices.
 
 use std::iter::Peekable;
 use integer_sqrt::IntegerSquareRoot;
-use crate::geom::Pt;
-use std::collections::HashSet;
+use rand::Rng;
+use crate::geom::{BBox, Pt};
 use std::cmp::Ordering;
 
 // Invariants:
@@ -13,13 +13,30 @@ use std::cmp::Ordering;
 // In Set2D: no leading/trailing empty lines (=> empty set is uniquely represented with lines.len() == 0)
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
-struct Run {
-	a: i64,
-	b: i64
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(into="(i64, i64)")]
+#[serde(from="(i64, i64)")]
+pub struct Run {
+	pub a: i64,
+	pub b: i64
+}
+
+impl From<Run> for (i64, i64) {
+	fn from(r: Run) -> Self {
+		(r.a, r.b)
+	}
+}
+
+impl From<(i64, i64)> for Run {
+	fn from((a, b): (i64, i64)) -> Self {
+		Run{a, b}
+	}
 }
 
 #[derive(Eq, PartialEq, Debug, Clone)]
-struct Set1D {
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Set1D {
 	runs: Vec<Run>
 }
 
@@ -104,10 +121,54 @@ impl Set1D {
 		}
 	}
 
+	// Runs of [a, b) not covered by self.
+	fn complement_in(&self, a: i64, b: i64) -> Set1D {
+		let mut runs = vec![];
+		let mut start = a;
+		for run in &self.runs {
+			if run.a > start {
+				runs.push(Run{a: start, b: run.a.min(b)});
+			}
+			start = start.max(run.b);
+			if start >= b {
+				break;
+			}
+		}
+		if start < b {
+			runs.push(Run{a: start, b});
+		}
+		runs.retain(|r| r.a < r.b);
+		Set1D{runs}
+	}
+
+	pub fn difference(&self, other: &Set1D) -> Set1D {
+		if self.is_empty() || other.is_empty() {
+			return self.clone();
+		}
+		let a = self.runs[0].a;
+		let b = self.runs.last().unwrap().b;
+		self.intersection(&other.complement_in(a, b))
+	}
+
+	// Sorted and disjoint.
+	pub fn runs(&self) -> &[Run] {
+		&self.runs
+	}
+
+	pub fn len(&self) -> i64 {
+		self.runs.iter().map(|r| r.b - r.a).sum()
+	}
+
+	pub fn translate(&self, dx: i64) -> Set1D {
+		Set1D{runs: self.runs.iter().map(|r| Run{a: r.a + dx, b: r.b + dx}).collect()}
+	}
+
+	#[allow(dead_code)]
 	pub fn alternative_union(&self, other: &Set1D) -> Set1D {
 		self.covered_by_at_least_n(other, 1)
 	}
 
+	#[allow(dead_code)]
 	pub fn alternative_intersection(&self, other: &Set1D) -> Set1D {
 		self.covered_by_at_least_n(other, 2)
 	}
@@ -171,11 +232,11 @@ impl Set1D {
 		Set1D { runs }
 	}
 
-	fn is_empty(&self) -> bool {
+	pub fn is_empty(&self) -> bool {
 		self.runs.is_empty()
 	}
 
-	fn empty() -> Set1D {
+	pub fn empty() -> Set1D {
 		Set1D{runs: vec![]}
 	}
 	
@@ -183,13 +244,15 @@ impl Set1D {
 		Set1D{runs: vec![Run{a, b}]}
 	}
 
+	#[cfg(test)]
 	fn from_pairs(pairs: &[(i64, i64)]) -> Set1D {
 		Set1D { runs: pairs.iter().map(|&(a, b)| Run { a, b }).collect() }
 	}
 }
 
-#[derive(Eq, PartialEq, Debug)]
-struct Set2D {
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Set2D {
 	y_start: i64,
 	lines: Vec<Set1D>
 }
@@ -199,6 +262,11 @@ impl Set2D {
 		self.y_start + self.lines.len() as i64
 	}
 
+	// Non-empty rows as (y, row), bottom to top.
+	pub fn lines(&self) -> impl Iterator<Item=(i64, &Set1D)> {
+		(self.y_start..).zip(self.lines.iter()).filter(|(_, line)| !line.is_empty())
+	}
+
 	fn push_line(&mut self, line: Set1D, empty_lines: &mut usize) {
 		if line.is_empty() {
 			*empty_lines += 1;
@@ -213,7 +281,15 @@ impl Set2D {
 		}
 	}
 
+	#[allow(dead_code)]
 	pub fn union(&self, other: &Set2D) -> Set2D {
+		// empty set's y_start is arbitrary
+		if self.is_empty() {
+			return other.clone();
+		}
+		if other.is_empty() {
+			return self.clone();
+		}
 		let y_start = self.y_start.min(other.y_start);
 		let y_end = self.y_end().max(other.y_end());
 		let self_range = self.y_start..self.y_end();
@@ -249,6 +325,114 @@ impl Set2D {
 		result
 	}
 
+	pub fn difference(&self, other: &Set2D) -> Set2D {
+		let mut result = Set2D{y_start: self.y_start, lines: vec![]};
+		let mut empty_lines = 0;
+		for (y, line) in (self.y_start..).zip(self.lines.iter()) {
+			let line = if other.y_start <= y && y < other.y_end() {
+				line.difference(&other.lines[(y - other.y_start) as usize])
+			} else {
+				line.clone()
+			};
+			result.push_line(line, &mut empty_lines);
+		}
+		result
+	}
+
+	// All lattice points of the bbox, inclusive.
+	pub fn rect(bbox: BBox) -> Set2D {
+		if bbox.min_x > bbox.max_x || bbox.min_y > bbox.max_y {
+			return Set2D::empty();
+		}
+		Set2D {
+			y_start: bbox.min_y,
+			lines: vec![Set1D::one_run(bbox.min_x, bbox.max_x + 1);
+						(bbox.max_y - bbox.min_y + 1) as usize],
+		}
+	}
+
+	#[allow(dead_code)]
+	pub fn complement(&self, bbox: BBox) -> Set2D {
+		Set2D::rect(bbox).difference(self)
+	}
+
+	#[allow(dead_code)]
+	pub fn translate(&self, d: Pt) -> Set2D {
+		Set2D {
+			y_start: self.y_start + d.y,
+			lines: self.lines.iter().map(|line| line.translate(d.x)).collect(),
+		}
+	}
+
+	// Number of points.
+	pub fn len(&self) -> i64 {
+		self.lines.iter().map(|line| line.len()).sum()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lines.is_empty()
+	}
+
+	// Uniformly random point, None for the empty set.
+	#[allow(dead_code)]
+	pub fn random_point(&self, rng: &mut impl Rng) -> Option<Pt> {
+		if self.is_empty() {
+			return None;
+		}
+		let mut k = rng.gen_range(0..self.len());
+		for (y, line) in self.lines() {
+			for run in line.runs() {
+				if k < run.b - run.a {
+					return Some(Pt{x: run.a + k, y});
+				}
+				k -= run.b - run.a;
+			}
+		}
+		unreachable!()
+	}
+
+	// Lattice points p with pt_in_poly(p, poly), i.e. boundary included.
+	pub fn from_polygon(poly: &[Pt]) -> Set2D {
+		let bbox = BBox::from_pts(poly);
+		let mut rows = vec![];
+		for y in bbox.min_y..=bbox.max_y {
+			let mut row = vec![];
+			// Crossings of the half-open edges, same rule as in pt_in_poly.
+			let mut crossings = vec![];
+			for (p, q) in crate::geom::poly_edges(poly) {
+				if p.y == q.y {
+					if p.y == y {
+						row.push((p.x.min(q.x), p.x.max(q.x)));
+					}
+					continue;
+				}
+				if y < p.y.min(q.y) || y > p.y.max(q.y) {
+					continue;
+				}
+				let mut num = p.x * (q.y - p.y) + (y - p.y) * (q.x - p.x);
+				let mut den = q.y - p.y;
+				if den < 0 {
+					num = -num;
+					den = -den;
+				}
+				if num % den == 0 {
+					row.push((num / den, num / den));
+				}
+				if y < p.y.max(q.y) {
+					crossings.push((num, den));
+				}
+			}
+			crossings.sort_by(|&(n1, d1), &(n2, d2)| (n1 * d2).cmp(&(n2 * d1)));
+			for c in crossings.chunks(2) {
+				let ((n1, d1), (n2, d2)) = (c[0], c[1]);
+				// strictly between
+				row.push((n1.div_euclid(d1) + 1, -(-n2).div_euclid(d2) - 1));
+			}
+			rows.push(row);
+		}
+		Set2D::from_rows(bbox.min_y, rows)
+	}
+
 	pub fn ring(x_center: i64, y_center: i64, d_min: i64, d_max: i64) -> Set2D {
 		let width = d_max.integer_sqrt();
 		let y_start = y_center - width;
@@ -281,10 +465,53 @@ impl Set2D {
 		result
 	}
 
+	// rows[i] lists inclusive x ranges of the row y_start + i,
+	// in any order, possibly overlapping.
+	pub fn from_rows(y_start: i64, rows: Vec<Vec<(i64, i64)>>) -> Set2D {
+		let mut result = Set2D{y_start, lines: vec![]};
+		let mut empty_lines = 0;
+		for mut row in rows {
+			row.retain(|&(a, b)| a <= b);
+			row.sort_unstable();
+			let mut runs: Vec<Run> = vec![];
+			for (a, b) in row {
+				match runs.last_mut() {
+					Some(last) if a <= last.b => last.b = last.b.max(b + 1),
+					_ => runs.push(Run{a, b: b + 1}),
+				}
+			}
+			result.push_line(Set1D{runs}, &mut empty_lines);
+		}
+		result
+	}
+
+	pub fn contains(&self, pt: Pt) -> bool {
+		if pt.y < self.y_start || pt.y >= self.y_end() {
+			return false;
+		}
+		let runs = &self.lines[(pt.y - self.y_start) as usize].runs;
+		let i = runs.partition_point(|r| r.b <= pt.x);
+		i < runs.len() && runs[i].a <= pt.x
+	}
+
+	// None for the empty set.
+	pub fn bbox(&self) -> Option<BBox> {
+		if self.lines.is_empty() {
+			return None;
+		}
+		let non_empty = || self.lines.iter().filter(|line| !line.is_empty());
+		Some(BBox {
+			min_x: non_empty().map(|line| line.runs[0].a).min().unwrap(),
+			max_x: non_empty().map(|line| line.runs.last().unwrap().b - 1).max().unwrap(),
+			min_y: self.y_start,
+			max_y: self.y_end() - 1,
+		})
+	}
+
 	pub fn as_points(&self) -> Vec<Pt> {
 		let mut result = vec![];
-		for (y, line) in (self.y_start..).zip(self.lines.iter()) {
-			for run in line.runs.iter() {
+		for (y, line) in self.lines() {
+			for run in line.runs() {
 				result.append(&mut (run.a..run.b).map(|x| Pt{x, y}).collect())
 			}
 		}
@@ -298,6 +525,7 @@ impl Set2D {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use super::*;
 
 	fn check_union_1_d(a: &[(i64, i64)], b: &[(i64, i64)], expected: &[(i64, i64)]) {
@@ -391,6 +619,149 @@ mod tests {
 		check_eq(0, 0, 79, 80);
 	}
 
+	#[test]
+	fn test_from_rows() {
+		let s = Set2D::from_rows(5, vec![
+			vec![],
+			vec![(3, 4), (0, 1), (2, 2), (7, 6)],
+			vec![],
+			vec![(10, 12), (11, 15)],
+			vec![],
+		]);
+		assert_eq!(s.y_start, 6);
+		assert_eq!(s.lines, vec![
+			Set1D::from_pairs(&[(0, 5)]),
+			Set1D::empty(),
+			Set1D::from_pairs(&[(10, 16)]),
+		]);
+		assert!(s.contains(Pt::new(0, 6)));
+		assert!(s.contains(Pt::new(4, 6)));
+		assert!(!s.contains(Pt::new(5, 6)));
+		assert!(!s.contains(Pt::new(-1, 6)));
+		assert!(!s.contains(Pt::new(12, 7)));
+		assert!(s.contains(Pt::new(15, 8)));
+		assert!(!s.contains(Pt::new(15, 9)));
+		let b = s.bbox().unwrap();
+		assert_eq!((b.min_x, b.max_x, b.min_y, b.max_y), (0, 15, 6, 8));
+		let rows: Vec<(i64, Vec<Run>)> = s.lines().map(|(y, line)| (y, line.runs().to_vec())).collect();
+		assert_eq!(rows, vec![(6, vec![Run{a: 0, b: 5}]), (8, vec![Run{a: 10, b: 16}])]);
+		assert!(Set2D::empty().bbox().is_none());
+		let ring = Set2D::ring(3, 4, 10, 20);
+		for x in -5..10 {
+			for y in -5..10 {
+				let pt = Pt::new(x, y);
+				let d = pt.dist2(Pt::new(3, 4));
+				assert_eq!(ring.contains(pt), (10..=20).contains(&d));
+			}
+		}
+	}
+
+	fn to_hash(s: &Set2D) -> HashSet<Pt> {
+		let points = s.as_points();
+		let result: HashSet<Pt> = points.iter().cloned().collect();
+		assert_eq!(result.len(), points.len());
+		assert_eq!(result.len() as i64, s.len());
+		result
+	}
+
+	// Some sets to combine, including empty ones and ones with gaps.
+	fn sample_sets() -> Vec<Set2D> {
+		vec![
+			Set2D::empty(),
+			Set2D::ring(0, 0, 10, 100),
+			Set2D::ring(3, -2, 0, 20),
+			Set2D::ring(30, 30, 5, 6),
+			Set2D::ring(0, 0, 79, 80),
+			Set2D::rect(BBox{min_x: -5, max_x: 7, min_y: -3, max_y: 12}),
+			Set2D::ring(0, 0, 10, 100).difference(&Set2D::ring(2, 1, 0, 30)),
+		]
+	}
+
+	#[test]
+	fn test_algebra() {
+		let bbox = BBox{min_x: -12, max_x: 15, min_y: -11, max_y: 14};
+		let all = to_hash(&Set2D::rect(bbox));
+		assert_eq!(all.len(), 28 * 26);
+		for a in &sample_sets() {
+			let ha = to_hash(a);
+			for b in &sample_sets() {
+				let hb = to_hash(b);
+				let union = a.union(b);
+				let intersection = a.intersection(b);
+				let difference = a.difference(b);
+				assert_eq!(to_hash(&union), ha.union(&hb).cloned().collect());
+				assert_eq!(to_hash(&intersection), ha.intersection(&hb).cloned().collect());
+				assert_eq!(to_hash(&difference), ha.difference(&hb).cloned().collect());
+				// canonical representation
+				assert_eq!(union.difference(&intersection), difference.union(&b.difference(a)));
+			}
+			let complement = a.complement(bbox);
+			assert_eq!(to_hash(&complement), all.difference(&ha).cloned().collect());
+			assert_eq!(to_hash(&a.translate(Pt::new(-3, 7))),
+					   ha.iter().map(|&p| Pt::new(p.x - 3, p.y + 7)).collect());
+			for &p in &all {
+				assert_eq!(a.contains(p), ha.contains(&p));
+			}
+			if let Some(b) = a.bbox() {
+				assert_eq!(a.intersection(&Set2D::rect(b)), *a);
+				assert_eq!(b.min_y, ha.iter().map(|p| p.y).min().unwrap());
+				assert_eq!(b.max_x, ha.iter().map(|p| p.x).max().unwrap());
+			}
+		}
+	}
+
+	#[test]
+	fn test_random_point() {
+		use rand::prelude::*;
+		let mut rng = StdRng::seed_from_u64(42);
+		assert_eq!(Set2D::empty().random_point(&mut rng), None);
+		let s = Set2D::ring(0, 0, 1, 2).union(&Set2D::ring(5, 5, 0, 0));
+		let mut cnt = std::collections::HashMap::new();
+		let n = 90_000;
+		for _ in 0..n {
+			let p = s.random_point(&mut rng).unwrap();
+			assert!(s.contains(p));
+			*cnt.entry(p).or_insert(0) += 1;
+		}
+		assert_eq!(cnt.len(), 9);
+		for &c in cnt.values() {
+			assert!((c as f64 - n as f64 / 9.0).abs() < 500.0, "{:?}", cnt);
+		}
+	}
+
+	#[test]
+	fn test_from_polygon() {
+		use crate::geom::pt_in_poly;
+		let polys = vec![
+			crate::prelude::load_problem(9).hole,
+			crate::prelude::load_problem(31).hole,
+			// comb with slanted teeth
+			vec![Pt::new(0, 0), Pt::new(10, 0), Pt::new(10, 10), Pt::new(8, 10),
+				 Pt::new(7, 4), Pt::new(6, 10), Pt::new(4, 10), Pt::new(3, 4),
+				 Pt::new(2, 10), Pt::new(0, 10), Pt::new(0, 5)],
+			// thin sliver without lattice points inside
+			vec![Pt::new(0, 0), Pt::new(7, 3), Pt::new(14, 7)],
+		];
+		for poly in &polys {
+			let s = Set2D::from_polygon(poly);
+			let b = BBox::from_pts(poly);
+			for x in b.min_x - 1..=b.max_x + 1 {
+				for y in b.min_y - 1..=b.max_y + 1 {
+					let p = Pt::new(x, y);
+					assert_eq!(s.contains(p), pt_in_poly(p, poly), "{:?}", p);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_serde() {
+		let s = Set2D::ring(0, 0, 1, 2);
+		let json = serde_json::to_string(&s).unwrap();
+		assert_eq!(json, r#"{"y_start":-1,"lines":[[[-1,2]],[[-1,0],[1,2]],[[-1,2]]]}"#);
+		assert_eq!(serde_json::from_str::<Set2D>(&json).unwrap(), s);
+	}
+
 	#[test]
 	fn test_union_2d() {
 		let empty = Set2D::empty();
@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use crate::geom::rotate_point;
+use crate::geom::{rotate_point, Transform, MAX_COORD};
 
 #[derive(serde::Deserialize)]
 pub struct RotateRequest {
@@ -8,21 +8,50 @@ pub struct RotateRequest {
     pub selected: Vec<bool>,
     pub pivot: Option<Pt>,
     pub angle: i16,
+    // Mirror x -> -x (relative to the pivot) before rotating.
+    #[serde(default)]
+    pub reflect: bool,
+    // Rotate by the Pythagorean angle cos = a / c, sin = b / c instead of `angle`.
+    #[serde(default)]
+    pub triple: Option<(i64, i64, i64)>,
 }
 
-pub fn rotate(r: &RotateRequest) -> Vec<Pt> {
+// Exact transform for the requested rotation, if there is one.
+// Multiples of 90 degrees and reflections keep all edge lengths,
+// Pythagorean rotations keep them whenever the images are lattice points.
+pub fn lattice_transform(r: &RotateRequest) -> Result<Option<Transform>, String> {
+    let reflection = Transform::lattice_symmetry(0, r.reflect);
+    match r.triple {
+        Some((a, b, c)) => match Transform::pythagorean(a, b, c) {
+            Some(t) => Ok(Some(t.compose(&reflection))),
+            None => Err(format!("{:?} is not a Pythagorean triple", (a, b, c))),
+        },
+        None if r.angle % 90 == 0 => Ok(Some(Transform::lattice_symmetry((r.angle / 90).into(), r.reflect))),
+        None => Ok(None),
+    }
+}
+
+/// Fails on malformed requests and on Pythagorean rotations that take
+/// a vertex off the lattice, rounding would change edge lengths.
+pub fn rotate(r: &RotateRequest) -> Result<Vec<Pt>, String> {
+    let n = r.problem.figure.vertices.len();
+    if r.vertices.len() != n || r.selected.len() != n {
+        return Err(format!("{} vertices and {} selection flags for a figure of {} vertices",
+                           r.vertices.len(), r.selected.len(), n));
+    }
+    let in_range = |p: &Pt| p.x.unsigned_abs() <= MAX_COORD as u64 && p.y.unsigned_abs() <= MAX_COORD as u64;
+    if let Some(p) = r.vertices.iter().chain(&r.pivot).find(|p| !in_range(p)) {
+        return Err(format!("{:?} is out of range", p));
+    }
     match r.pivot {
         None => center_of_mass_rotation(r),
         Some(pivot) => pivot_rotation(r, pivot),
     }
 }
 
-pub fn center_of_mass_rotation(r: &RotateRequest) -> Vec<Pt> {
-    let mut vs = r.vertices.clone();
-    assert_eq!(vs.len(), r.selected.len());
-
+pub fn center_of_mass_rotation(r: &RotateRequest) -> Result<Vec<Pt>, String> {
     let mut sels = Vec::new();
-    for (v, &sel) in vs.iter_mut().zip(r.selected.iter()) {
+    for (v, &sel) in r.vertices.iter().zip(r.selected.iter()) {
         if sel {
             sels.push(v);
         }
@@ -35,25 +64,49 @@ pub fn center_of_mass_rotation(r: &RotateRequest) -> Vec<Pt> {
 
     let com_pivot = Pt::new(com_x.round() as i64, com_y.round() as i64);
 
-    for (v, &sel) in vs.iter_mut().zip(r.selected.iter()) {
-        if sel {
-            *v = rotate_point(*v, com_pivot, r.angle)
-        }
-    }
-    vs
-
+    pivot_rotation(r, com_pivot)
 }
 
-pub fn pivot_rotation(r: &RotateRequest, pivot: Pt) -> Vec<Pt> {
+pub fn pivot_rotation(r: &RotateRequest, pivot: Pt) -> Result<Vec<Pt>, String> {
     let mut vs = r.vertices.clone();
-    assert_eq!(vs.len(), r.selected.len());
 
-    for (v, &sel) in vs.iter_mut().zip(r.selected.iter()) {
+    let exact = lattice_transform(r)?.map(|t| t.around(pivot));
+    let reflection = Transform::lattice_symmetry(0, r.reflect).around(pivot);
+    for (i, (v, &sel)) in vs.iter_mut().zip(r.selected.iter()).enumerate() {
         if sel {
-            *v = rotate_point(*v, pivot, r.angle)
+            *v = match exact {
+                Some(t) => t.apply(*v).ok_or_else(|| format!(
+                    "inexact rotation: vertex {} {:?} doesn't go to a lattice point", i, v))?,
+                None => rotate_point(reflection.apply(*v).unwrap(), pivot, r.angle),
+            }
         }
     }
-    vs
-
+    Ok(vs)
+}
 
+#[cfg(test)]
+#[test]
+fn test_rotate() {
+    let vertices = vec![Pt::new(0, 0), Pt::new(5, 0), Pt::new(1, 0)];
+    let r = RotateRequest {
+        problem: Problem {
+            hole: vec![],
+            figure: Figure { vertices: vertices.clone(), edges: vec![] },
+            epsilon: 0,
+            bonuses: vec![],
+        },
+        vertices,
+        selected: vec![true, true, false],
+        pivot: Some(Pt::new(0, 0)),
+        angle: 0,
+        reflect: false,
+        triple: Some((3, 4, 5)),
+    };
+    assert_eq!(rotate(&r).unwrap(), vec![Pt::new(0, 0), Pt::new(3, 4), Pt::new(1, 0)]);
+    let inexact = RotateRequest { selected: vec![true; 3], ..r };
+    assert!(rotate(&inexact).unwrap_err().contains("inexact"));
+    let bad_triple = RotateRequest { triple: Some((1, 1, 1)), ..inexact };
+    assert!(rotate(&bad_triple).is_err());
+    let bad_selection = RotateRequest { triple: None, selected: vec![], ..bad_triple };
+    assert!(rotate(&bad_selection).is_err());
 }
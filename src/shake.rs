@@ -1,33 +1,81 @@
-use rand::Rng;
+use rand::prelude::*;
 use crate::prelude::*;
+use crate::cancel::{CancelToken, StopReason};
+use crate::progress::Progress;
 
 #[derive(serde::Deserialize)]
+#[derive(Clone)]
 pub struct ShakeRequest {
     pub problem: Problem,
     pub vertices: Vec<Pt>,
     pub selected: Vec<bool>,
     pub method: String,
     pub param: i64,
+    #[serde(default)] pub seed: Option<u64>,
+    // Methods that run for long check it and return the best pose so far.
+    #[serde(skip)] pub stop: CancelToken,
+    #[serde(skip)] pub progress: Progress,
 }
 
-pub fn shake(r: &ShakeRequest) -> Vec<Pt> {
-    match r.method.as_str() {
+#[derive(serde::Serialize)]
+pub struct ShakeResponse {
+    pub vertices: Vec<Pt>,
+    pub stop_reason: StopReason,
+    pub seed: u64,  // the one used, to replay the shake
+}
+
+impl ShakeRequest {
+    /// Same seed, same shake. Without a seed every call is different.
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    // The methods index vertices and selected by figure vertex, so a pose
+    // from the client that doesn't fit the figure is rejected here.
+    fn validate(&self) -> Result<(), String> {
+        let n = self.problem.figure.vertices.len();
+        if self.vertices.len() != n || self.selected.len() != n {
+            return Err(format!("{} vertices and {} selected for a figure of {} vertices",
+                               self.vertices.len(), self.selected.len(), n));
+        }
+        Ok(())
+    }
+}
+
+/// Picks a seed if there is none and returns it, so that any shake can be replayed.
+pub fn shake(r: &ShakeRequest) -> Result<ShakeResponse, String> {
+    r.validate()?;
+    let seed = r.seed.unwrap_or_else(rand::random);
+    let r = &ShakeRequest { seed: Some(seed), ..r.clone() };
+    let vertices = match r.method.as_str() {
         "random" => random_shake(r),
         "banana" => crate::banana::banana_shake(r),
         "ice" => crate::ice::ice_shake(r),
         "mango" => crate::mango::mango_shake(r),
-        "greedy" => crate::greedy::greedy_shake(r),
+        "greedy" => crate::greedy::greedy_shake(r)?,
         "springs" => crate::springs::springs(r),
-        "threshold" => crate::threshold::threshold_shake(r),
+        "threshold" => crate::threshold::threshold_shake(r)?,
         "daiquiri" => crate::daiquiri::daikuiri_shake(r, false),
         "mojito" => crate::daiquiri::daikuiri_shake(r, true),
         "bruteforce" => crate::bruteforce::brutforce(r),
-        s => panic!("{:?}", s),
-    }
+        s => return Err(format!("unknown shake method {:?}", s)),
+    };
+    Ok(ShakeResponse { vertices, stop_reason: r.stop.stop_reason(), seed })
+}
+
+/// For methods that only move from one valid pose to another: what's wrong with the start.
+pub fn invalid_start(r: &ShakeRequest) -> String {
+    let pose = Pose { vertices: r.vertices.clone(), bonuses: vec![] };
+    let violations: Vec<String> = crate::checker::check_pose(&r.problem, &pose)
+        .violations.iter().map(|v| v.to_string()).collect();
+    format!("{} needs a valid starting pose: {}", r.method, violations.join("; "))
 }
 
 fn random_shake(r: &ShakeRequest) -> Vec<Pt> {
-    let mut rng = rand::thread_rng();
+    let mut rng = r.rng();
 
     let mut vs = r.vertices.clone();
     assert_eq!(vs.len(), r.selected.len());
@@ -38,4 +86,41 @@ fn random_shake(r: &ShakeRequest) -> Vec<Pt> {
         }
     }
     vs
-}
\ No newline at end of file
+}
+#[cfg(test)]
+#[test]
+fn test_shake_stops() {
+    let mut problem = load_problem(11);
+    problem.bonuses.clear();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let r = ShakeRequest {
+        vertices: problem.figure.vertices.clone(),
+        selected: vec![true; problem.figure.vertices.len()],
+        problem,
+        method: "ice".to_string(),
+        param: 5,
+        seed: Some(1),
+        stop: CancelToken::with_time_limit(Some(std::time::Duration::from_millis(50))),
+        progress: Progress::new("ice").with_sink(crate::progress::ChannelSink(tx)),
+    };
+    let start = std::time::Instant::now();
+    let res = shake(&r).unwrap();
+    assert!(start.elapsed().as_secs() < 5);
+    assert_eq!(res.stop_reason, StopReason::TimedOut);
+    assert_eq!(res.seed, 1);
+    assert_eq!(res.vertices.len(), r.vertices.len());
+
+    let bad = ShakeRequest { selected: vec![true], ..r.clone() };
+    assert!(shake(&bad).is_err());
+    let collapsed = ShakeRequest {
+        vertices: vec![r.vertices[0]; r.vertices.len()],
+        method: "greedy".to_string(),
+        ..r.clone()
+    };
+    assert!(shake(&collapsed).is_err_and(|e| e.contains("valid starting pose")));
+
+    drop((r, bad, collapsed));
+    let events: Vec<_> = rx.iter().collect();
+    assert!(!events.is_empty());
+    assert!(events.windows(2).all(|w| w[0].iteration < w[1].iteration && w[1].best <= w[0].best));
+}
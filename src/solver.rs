@@ -1,55 +1,365 @@
-#![allow(dead_code)]
+// Every algorithm behind one interface, see solvers() for the list.
+//
+// Each solver has typed parameters (its own struct, filled from JSON
+// on top of its defaults), may start from a given pose, and gets a
+// CancelToken with an optional deadline. Shake methods run once, or until
+// stopped; searches that keep finding poses (rail, multishaker) return
+// the best valid one found before being stopped, or the first one
+// if there is no deadline. How they are doing meanwhile goes to a Progress.
+// Every solver takes a seed; if it's not given one is picked and
+// recorded next to the solution, so that any run can be replayed.
 
 use core::time::Duration;
+use std::sync::OnceLock;
+use std::time::Instant;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use crate::prelude::*;
-use crate::prelude::Problem;
+use crate::cancel::{CancelToken, StopReason};
+use crate::checker::check_pose;
+use crate::progress::{JsonlSink, Progress, StderrSink};
 use crate::random::RandomSolver;
-use crate::util::{load_problem, store_solution};
+use crate::util::{flag_value, store_solution};
 
 #[derive(Debug)]
 pub enum SolverError {
     SolverOutOfTime(Duration),
+    Cancelled,
     Other(String),
 }
 
+impl SolverError {
+    /// For solvers that were stopped before finding anything.
+    pub fn stopped(stop: &CancelToken, start_time: Instant) -> SolverError {
+        match stop.stop_reason() {
+            StopReason::Cancelled => SolverError::Cancelled,
+            _ => SolverError::SolverOutOfTime(start_time.elapsed()),
+        }
+    }
+}
+
+impl std::fmt::Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::SolverOutOfTime(d) => write!(f, "nothing found in {:.1}s", d.as_secs_f64()),
+            SolverError::Cancelled => write!(f, "cancelled"),
+            SolverError::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
 pub trait Solver {
-    fn solve(&mut self, problem: &Problem, duration_limit: Option<Duration>) -> Result<Pose, SolverError>;
+    /// `start` is a pose to improve on, solvers that build poses
+    /// from scratch ignore it.
+    fn solve(&mut self, problem: &Problem, start: Option<&Pose>, stop: &CancelToken, progress: &Progress) -> Result<Pose, SolverError>;
+}
+
+pub fn start_vertices(problem: &Problem, start: Option<&Pose>) -> Result<Vec<Pt>, SolverError> {
+    match start {
+        None => Ok(problem.figure.vertices.clone()),
+        Some(pose) if pose.vertices.len() == problem.figure.vertices.len() => Ok(pose.vertices.clone()),
+        Some(pose) => Err(SolverError::Other(format!(
+            "starting pose has {} vertices instead of {}", pose.vertices.len(), problem.figure.vertices.len()))),
+    }
+}
+
+/// Runs a search that reports poses through a callback and returns
+/// the valid one with the fewest dislikes. Every pose is a progress event.
+pub fn best_found(
+    problem: &Problem,
+    stop: &CancelToken,
+    progress: &Progress,
+    search: impl FnOnce(&mut dyn FnMut(&Pose) -> bool),
+) -> Result<Pose, SolverError> {
+    let start_time = Instant::now();
+    let mut best: Option<(i64, Pose)> = None;
+    let mut iteration = 0;
+    search(&mut |pose| {
+        let check = check_pose(problem, pose);
+        if check.valid && best.as_ref().is_none_or(|(d, _)| check.dislikes < *d) {
+            best = Some((check.dislikes, pose.clone()));
+        }
+        progress.report(iteration, check.valid.then_some(check.dislikes as f64),
+                        best.as_ref().map(|(d, _)| *d as f64), Some(check.violations.len()));
+        iteration += 1;
+        stop.deadline().is_some() || best.is_none()
+    });
+    best.map(|(_, pose)| pose).ok_or_else(|| SolverError::stopped(stop, start_time))
+}
+
+// One shake::shake() call with all vertices selected.
+struct ShakeSolver {
+    method: &'static str,
+    config: ShakeConfig,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShakeConfig {
+    param: i64,
+    seed: Option<u64>,
+}
+
+impl Solver for ShakeSolver {
+    fn solve(&mut self, problem: &Problem, start: Option<&Pose>, stop: &CancelToken, progress: &Progress) -> Result<Pose, SolverError> {
+        let vertices = start_vertices(problem, start)?;
+        let r = crate::shake::ShakeRequest {
+            problem: problem.clone(),
+            selected: vec![true; vertices.len()],
+            vertices,
+            method: self.method.to_string(),
+            param: self.config.param,
+            seed: self.config.seed,
+            stop: stop.clone(),
+            progress: progress.clone(),
+        };
+        let bonuses = start.map(|pose| pose.bonuses.clone()).unwrap_or_default();
+        let res = crate::shake::shake(&r).map_err(SolverError::Other)?;
+        Ok(Pose { vertices: res.vertices, bonuses })
+    }
+}
+
+type MakeSolver = dyn Fn(serde_json::Value) -> Result<Box<dyn Solver>, String> + Send + Sync;
+
+pub struct SolverEntry {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub defaults: serde_json::Value,
+    make: Box<MakeSolver>,
+}
+
+impl SolverEntry {
+    fn new<C: Serialize + DeserializeOwned + 'static>(
+        name: &'static str,
+        about: &'static str,
+        defaults: C,
+        make: impl Fn(C) -> Box<dyn Solver> + Send + Sync + 'static,
+    ) -> SolverEntry {
+        SolverEntry {
+            name,
+            about,
+            defaults: serde_json::to_value(defaults).unwrap(),
+            make: Box::new(move |params| {
+                serde_json::from_value(params).map(&make).map_err(|e| e.to_string())
+            }),
+        }
+    }
+
+    /// All the parameters: the defaults overridden by `params`
+    /// (a JSON object or null), with a random seed if there was none.
+    pub fn full_params(&self, params: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let mut config = self.defaults.clone();
+        let m = config.as_object_mut().unwrap();
+        match params {
+            serde_json::Value::Null => {}
+            serde_json::Value::Object(params) => {
+                for (k, v) in params {
+                    m.insert(k.clone(), v.clone());
+                }
+            }
+            _ => return Err(format!("{} parameters must be an object, got {}", self.name, params)),
+        }
+        if m.get("seed").is_none_or(|seed| seed.is_null()) {
+            m.insert("seed".to_string(), rand::random::<u64>().into());
+        }
+        Ok(config)
+    }
+
+    /// The solver with full_params(params), same params and seed make the same run.
+    pub fn make(&self, params: &serde_json::Value) -> Result<Box<dyn Solver>, String> {
+        let config = self.full_params(params)?;
+        (self.make)(config).map_err(|e| format!("{}: {}", self.name, e))
+    }
+}
+
+pub fn solvers() -> &'static [SolverEntry] {
+    static SOLVERS: OnceLock<Vec<SolverEntry>> = OnceLock::new();
+    SOLVERS.get_or_init(|| {
+        let mut v = vec![
+            SolverEntry::new("random", "random poses in the figure's bounding box until one is valid",
+                RandomSolver::default(), |c| Box::new(c)),
+            SolverEntry::new("rail", "places vertices most constrained first",
+                crate::rail::RailSolver::default(), |c| Box::new(c)),
+            SolverEntry::new("rail_constrained", "rail that fills the hole corners first",
+                crate::rail_constrained::RailConstrainedSolver::default(), |c| Box::new(c)),
+            SolverEntry::new("multishaker", "tucks the pose in, then greedy and threshold shakes",
+                crate::multishaker::MultishakerSolver::default(), |c| Box::new(c)),
+        ];
+        // (name, shake method, default param)
+        let shakes: &[(&'static str, &'static str, i64)] = &[
+            ("random_shake", "random", 5), ("banana", "banana", 5), ("ice", "ice", 5),
+            ("mango", "mango", 5), ("greedy", "greedy", 2), ("springs", "springs", 5),
+            ("threshold", "threshold", 2), ("daiquiri", "daiquiri", 5), ("mojito", "mojito", 5),
+            ("bruteforce", "bruteforce", 5),
+        ];
+        for &(name, method, param) in shakes {
+            v.push(SolverEntry::new(name, "shake method, runs once or until stopped",
+                ShakeConfig { param, seed: None }, move |config| Box::new(ShakeSolver { method, config })));
+        }
+        v
+    })
+}
+
+pub fn get_solver(name: &str) -> Option<&'static SolverEntry> {
+    solvers().iter().find(|e| e.name == name)
+}
+
+/// What viz_server's /api/solve takes.
+#[derive(serde::Deserialize)]
+#[derive(Clone)]
+pub struct SolveRequest {
+    pub solver: String,
+    pub problem: Problem,
+    #[serde(default)] pub start: Option<Pose>,
+    #[serde(default)] pub params: serde_json::Value,
+}
+
+#[derive(serde::Serialize)]
+pub struct SolveResponse {
+    pub pose: Pose,
+    pub params: serde_json::Value,  // with the seed, to replay the run
+    pub stop_reason: StopReason,
+}
+
+pub fn run_solve_request(r: &SolveRequest, stop: &CancelToken, progress: &Progress) -> Result<SolveResponse, String> {
+    let entry = get_solver(&r.solver).ok_or_else(|| format!("no solver {:?}", r.solver))?;
+    let params = entry.full_params(&r.params)?;
+    let mut solver = entry.make(&params)?;
+    let pose = solver.solve(&r.problem, r.start.as_ref(), stop, progress)
+        .map_err(|e| e.to_string())?;
+    Ok(SolveResponse { pose, params, stop_reason: stop.stop_reason() })
 }
 
 crate::entry_point!("solver", solver_main);
 fn solver_main() {
-    let problem_no: i32 = match std::env::args().nth(2) {
-        Some(p) => p.parse().unwrap(),
+    if std::env::args().any(|a| a == "--list") {
+        for e in solvers() {
+            println!("{:<17} {}  {}", e.name, e.defaults, e.about);
+        }
+        return;
+    }
+    let problem_no: i32 = match std::env::args().nth(2).and_then(|p| p.parse().ok()) {
+        Some(p) => p,
         None => {
             eprintln!("Usage:");
-            eprintln!("    cargo run solver 11");
-            eprintln!("    Env vars:");
-            eprintln!("    SOLVER: one of: random, ..");
-            eprintln!("    DURATION_LIMIT_SECONDS: time limit for solver");
+            eprintln!("    cargo run solver 11 [--solver rail] [--params '{{\"superflex\": true}}']");
+            eprintln!("        [--start pose.json] [--time SECONDS] [--progress-jsonl]");
+            eprintln!("    cargo run solver --list");
+            eprintln!("    Env vars SOLVER and DURATION_LIMIT_SECONDS work too.");
             std::process::exit(1);
         }
     };
 
-    let mut solver = match std::env::var("SOLVER") {
-        Ok(solver_name) => match solver_name.as_str() {
-            "random" => Box::new(RandomSolver {}),
-            _ => Box::new(RandomSolver {}),
+    let name = flag_value("solver").or_else(|| std::env::var("SOLVER").ok())
+        .unwrap_or_else(|| "random".to_string());
+    let params: serde_json::Value = match flag_value("params") {
+        Some(s) => serde_json::from_str(&s).unwrap_or_else(|e| panic!("--params {}: {}", s, e)),
+        None => serde_json::Value::Null,
+    };
+    let (params, mut solver) = match get_solver(&name).ok_or_else(|| format!("no solver {:?}, see --list", name))
+        .and_then(|e| {
+            let params = e.full_params(&params)?;
+            Ok((params.clone(), e.make(&params)?))
+        }) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        _ => Box::new(RandomSolver {}),
     };
+    eprintln!("{} {}", name, params);
 
-    let duration_per_task = std::env::var("DURATION_LIMIT_SECONDS").ok().and_then(|duration_limit| {
-        if let Ok(duration_limit) = duration_limit.parse::<u64>() {
-            Some(Duration::from_secs(duration_limit))
-        } else {
-            None
-        }
+    let duration_limit = flag_value("time").or_else(|| std::env::var("DURATION_LIMIT_SECONDS").ok())
+        .map(|s| Duration::from_secs_f64(s.parse().unwrap_or_else(|_| panic!("time limit {:?}", s))));
+    let start: Option<Pose> = flag_value("start").map(|path| {
+        let data = std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        serde_json::from_slice(&data).unwrap()
     });
 
+    // --progress-jsonl keeps the convergence curve
+    let mut progress = Progress::new(&name).with_sink(StderrSink::default());
+    if std::env::args().any(|a| a == "--progress-jsonl") {
+        let seed = &params["seed"];
+        let path = format!("outputs/progress/{}_{}_{}.jsonl", problem_no, name, seed);
+        progress = progress.with_sink(JsonlSink::create(&path).unwrap());
+        eprintln!("progress goes to {}", path);
+    }
+
     let problem: Problem = load_problem(problem_no);
-    match solver.solve(&problem, duration_per_task) {
-        Ok(pose) => store_solution(problem_no, &pose),
-        Err(err) => eprintln!("Error: {:?}", err),
+    match solver.solve(&problem, start.as_ref(), &CancelToken::with_time_limit(duration_limit), &progress) {
+        Ok(pose) => {
+            let check = check_pose(&problem, &pose);
+            eprintln!("{}, {} dislikes", if check.valid { "valid" } else { "not valid" }, check.dislikes);
+            store_solution(problem_no, &pose);
+            store_run(problem_no, &name, &params, check.dislikes);
+        }
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
+
+/// Next to the solution, what it takes to replay the run that found it.
+pub fn store_run(problem_no: i32, solver: &str, params: &serde_json::Value, dislikes: i64) {
+    let run = serde_json::json!({"solver": solver, "params": params, "dislikes": dislikes});
+    let path = format!("outputs/sol_{}.run.json", problem_no);
+    std::fs::write(project_path(&path), serde_json::to_vec(&run).unwrap()).unwrap();
+    eprintln!("solver and seed saved to {}", path);
+}
+
+#[cfg(test)]
+#[test]
+fn test_solvers() {
+    let p = load_problem(11);
+    for e in solvers() {
+        assert!(e.make(&serde_json::Value::Null).is_ok(), "{}", e.name);
     }
-}
\ No newline at end of file
+    assert!(get_solver("rail").unwrap().make(&serde_json::json!({"superflex": 1})).is_err());
+    assert!(get_solver("rail").unwrap().make(&serde_json::json!({"nope": true})).is_err());
+    assert!(get_solver("greedy").unwrap().make(&serde_json::json!([])).is_err());
+
+    let r: SolveRequest = serde_json::from_value(serde_json::json!({
+        "solver": "rail",
+        "problem": serde_json::to_value(&p).unwrap(),
+    })).unwrap();
+    let stop = CancelToken::with_time_limit(Some(Duration::from_millis(200)));
+    let (tx, rx) = std::sync::mpsc::channel();
+    let progress = Progress::new("rail").with_sink(crate::progress::ChannelSink(tx));
+    let res = run_solve_request(&r, &stop, &progress).unwrap();
+    assert_eq!(res.stop_reason, StopReason::TimedOut);
+    let pose = res.pose;
+    assert!(check_pose(&p, &pose).valid);
+    drop(progress);
+    let events: Vec<_> = rx.iter().collect();
+    assert!(!events.is_empty());
+    assert_eq!(events.last().unwrap().best, Some(check_pose(&p, &pose).dislikes as f64));
+
+    let r = SolveRequest {
+        solver: "greedy".to_string(),
+        problem: p.clone(),
+        start: Some(pose),
+        params: serde_json::json!({"param": 1}),
+    };
+    let stop = CancelToken::default();
+    let quiet = Progress::default();
+    let res = run_solve_request(&r, &stop, &quiet).unwrap();
+    assert!(check_pose(&p, &res.pose).valid);
+
+    // the recorded params replay the run
+    let replay = SolveRequest { params: res.params.clone(), ..r.clone() };
+    let res2 = run_solve_request(&replay, &stop, &quiet).unwrap();
+    assert_eq!(res2.params, res.params);
+    assert_eq!(res2.pose.vertices, res.pose.vertices);
+    for name in ["rail", "multishaker", "mojito"] {
+        let r = SolveRequest { solver: name.to_string(), params: serde_json::json!({"seed": 7}), ..r.clone() };
+        assert_eq!(run_solve_request(&r, &stop, &quiet).unwrap().pose.vertices,
+                   run_solve_request(&r, &stop, &quiet).unwrap().pose.vertices, "{}", name);
+    }
+
+    let bad_start = SolveRequest { start: Some(Pose { vertices: vec![], bonuses: vec![] }), ..r.clone() };
+    assert!(run_solve_request(&bad_start, &stop, &quiet).is_err());
+
+    let cancelled = CancelToken::default();
+    cancelled.cancel();
+    let r = SolveRequest { solver: "rail".to_string(), params: serde_json::Value::Null, ..r };
+    assert!(matches!(run_solve_request(&r, &cancelled, &quiet), Err(e) if e == "cancelled"));
+}
@@ -8,7 +8,7 @@ fn orig_distance(problem: &Problem, v1_id: usize, v2_id: usize) -> i64 {
 }
 
 pub fn springs(r: &ShakeRequest) -> Vec<Pt> {
-    let rng = &mut rand::thread_rng();
+    let rng = &mut r.rng();
     let mut result = r.vertices.clone();
     let rigidity = 0.2;
 
@@ -18,7 +18,8 @@ pub fn springs(r: &ShakeRequest) -> Vec<Pt> {
         .collect();
     selected_idxs.shuffle(rng);
 
-    for v_id in selected_idxs {
+    // score is how far the spring forces move each vertex
+    for (i, v_id) in selected_idxs.into_iter().enumerate() {
         let mut dx = 0.0;
         let mut dy = 0.0;
         for n_id in neighbours(&r.problem.figure.edges, v_id) {
@@ -29,7 +30,7 @@ pub fn springs(r: &ShakeRequest) -> Vec<Pt> {
             dx += rigidity * ((n - v).x as f64 / current_dist * (current_dist - orig_distance));
             dy += rigidity * ((n - v).y as f64 / current_dist * (current_dist - orig_distance));
         }
-        eprintln!("dx: {:?} dy: {:?}", dx, dy);
+        r.progress.report(i as u64, Some((dx * dx + dy * dy).sqrt()), None, None);
         result[v_id] = Pt {
             x: result[v_id].x + dx.round() as i64,
             y: result[v_id].y + dy.round() as i64
@@ -56,7 +56,7 @@ fn summary() {
     let mut best_norm_sum = 0;
     let mut latest_norm_sum = 0;
 
-    for problem_id in all_problem_ids() {
+    for problem_id in crate::registry::selected_problem_ids() {
         dbg!(problem_id);
         let p = load_problem(problem_id);
 
@@ -195,7 +195,7 @@ fn summary() {
 
 fn get_bonus_list() -> HashMap<i32, Vec<String>> {
     let mut bonuses: HashMap<i32, Vec<String>> = HashMap::new();
-    for problem_id in all_problem_ids() {
+    for problem_id in crate::registry::selected_problem_ids() {
         let p = load_problem(problem_id);
         for b in p.bonuses {
             bonuses.entry(b.problem)
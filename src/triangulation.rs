@@ -0,0 +1,227 @@
+// Ear clipping triangulation of the hole and point location on top of it.
+// Also uniformly samples lattice points of the hole
+// without scanning its whole bounding box.
+
+use rand::Rng;
+use crate::prelude::*;
+use crate::geom::{orientation, poly_edges, BBox};
+
+pub struct Triangulation {
+    pub triangles: Vec<[Pt; 3]>,  // counterclockwise (orientation > 0)
+    bbox: BBox,
+    // triangle indices whose y-range overlaps the slab
+    slabs: Vec<Vec<usize>>,
+    slab_height: i64,
+    rows: Vec<TriangleRows>,
+    // prefix sums of lattice point counts over triangles
+    count_prefix: Vec<u64>,
+}
+
+// Lattice points of a closed triangle, row by row.
+struct TriangleRows {
+    y_start: i64,
+    runs: Vec<(i64, i64)>,  // inclusive x range, empty if x1 < x0
+    prefix: Vec<u64>,  // prefix[i] = number of points in rows before i
+}
+
+impl TriangleRows {
+    fn new(tri: &[Pt; 3]) -> TriangleRows {
+        let y_start = tri.iter().map(|p| p.y).min().unwrap();
+        let y_end = tri.iter().map(|p| p.y).max().unwrap();
+        let mut runs = vec![];
+        let mut prefix = vec![0];
+        for y in y_start..=y_end {
+            let mut x0 = i64::MAX;
+            let mut x1 = i64::MIN;
+            for (p, q) in poly_edges(tri) {
+                if y < p.y.min(q.y) || y > p.y.max(q.y) {
+                    continue;
+                }
+                if p.y == q.y {
+                    x0 = x0.min(p.x.min(q.x));
+                    x1 = x1.max(p.x.max(q.x));
+                    continue;
+                }
+                let mut num = p.x * (q.y - p.y) + (y - p.y) * (q.x - p.x);
+                let mut den = q.y - p.y;
+                if den < 0 {
+                    num = -num;
+                    den = -den;
+                }
+                x0 = x0.min(-(-num).div_euclid(den));
+                x1 = x1.max(num.div_euclid(den));
+            }
+            runs.push((x0, x1));
+            prefix.push(prefix.last().unwrap() + (x1 - x0 + 1).max(0) as u64);
+        }
+        TriangleRows { y_start, runs, prefix }
+    }
+
+    fn count(&self) -> u64 {
+        *self.prefix.last().unwrap()
+    }
+
+    fn nth(&self, k: u64) -> Pt {
+        let i = self.prefix.partition_point(|&c| c <= k) - 1;
+        let (x0, _) = self.runs[i];
+        Pt::new(x0 + (k - self.prefix[i]) as i64, self.y_start + i as i64)
+    }
+}
+
+pub fn pt_in_triangle(pt: Pt, tri: &[Pt; 3]) -> bool {
+    orientation(tri[0], tri[1], pt) >= 0 &&
+    orientation(tri[1], tri[2], pt) >= 0 &&
+    orientation(tri[2], tri[0], pt) >= 0
+}
+
+// Triangulates a simple polygon (either orientation) by ear clipping.
+// Returns n - 2 counterclockwise triangles with vertices from the polygon.
+pub fn triangulate(poly: &[Pt]) -> Vec<[Pt; 3]> {
+    let mut vs = poly.to_vec();
+    let area: i64 = poly_edges(poly).map(|(a, b)| a.cross(b)).sum();
+    if area < 0 {
+        vs.reverse();
+    }
+
+    let mut triangles = vec![];
+    while vs.len() > 3 {
+        let n = vs.len();
+        let ear = (0..n).find(|&i| {
+            let tri = [vs[(i + n - 1) % n], vs[i], vs[(i + 1) % n]];
+            orientation(tri[0], tri[1], tri[2]) > 0 &&
+            vs.iter().all(|&p| tri.contains(&p) || !pt_in_triangle(p, &tri))
+        }).expect("polygon is not simple");
+        triangles.push([vs[(ear + n - 1) % n], vs[ear], vs[(ear + 1) % n]]);
+        vs.remove(ear);
+    }
+    assert!(orientation(vs[0], vs[1], vs[2]) > 0, "polygon is not simple");
+    triangles.push([vs[0], vs[1], vs[2]]);
+    triangles
+}
+
+impl Triangulation {
+    pub fn new(hole: &[Pt]) -> Triangulation {
+        let triangles = triangulate(hole);
+        let bbox = BBox::from_pts(hole);
+
+        let height = bbox.max_y - bbox.min_y + 1;
+        let num_slabs = ((triangles.len() as f64).sqrt().ceil() as i64).min(height);
+        let slab_height = (height + num_slabs - 1) / num_slabs;
+        let mut slabs = vec![vec![]; num_slabs as usize];
+        for (i, tri) in triangles.iter().enumerate() {
+            let y1 = tri.iter().map(|p| p.y).min().unwrap();
+            let y2 = tri.iter().map(|p| p.y).max().unwrap();
+            for s in (y1 - bbox.min_y) / slab_height..=(y2 - bbox.min_y) / slab_height {
+                slabs[s as usize].push(i);
+            }
+        }
+
+        let rows: Vec<TriangleRows> = triangles.iter().map(TriangleRows::new).collect();
+        let mut count_prefix = vec![0];
+        for r in &rows {
+            count_prefix.push(count_prefix.last().unwrap() + r.count());
+        }
+
+        Triangulation { triangles, bbox, slabs, slab_height, rows, count_prefix }
+    }
+
+    fn slab(&self, y: i64) -> &[usize] {
+        if y < self.bbox.min_y || y > self.bbox.max_y {
+            return &[];
+        }
+        &self.slabs[((y - self.bbox.min_y) / self.slab_height) as usize]
+    }
+
+    /// Index of the first triangle containing the point (boundary included),
+    /// None if the point is outside the hole.
+    pub fn locate(&self, pt: Pt) -> Option<usize> {
+        self.slab(pt.y).iter().copied().find(|&i| pt_in_triangle(pt, &self.triangles[i]))
+    }
+
+    /// Uniformly random lattice point of the hole (boundary included).
+    pub fn random_point(&self, rng: &mut impl Rng) -> Pt {
+        let total = *self.count_prefix.last().unwrap();
+        assert!(total > 0);
+        loop {
+            // Points on the diagonals are counted by several triangles,
+            // only the one locate() gives keeps them.
+            let k = rng.gen_range(0..total);
+            let i = self.count_prefix.partition_point(|&c| c <= k) - 1;
+            let pt = self.rows[i].nth(k - self.count_prefix[i]);
+            if self.locate(pt) == Some(i) {
+                return pt;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use rand::prelude::*;
+    use crate::geom::{pt_in_poly, poly_area_doubled};
+    use crate::rle::Set2D;
+    use super::*;
+
+    // Union of the triangle rows.
+    fn lattice_set(t: &Triangulation) -> Set2D {
+        let mut rows = vec![vec![]; (t.bbox.max_y - t.bbox.min_y + 1) as usize];
+        for r in &t.rows {
+            for (i, &run) in r.runs.iter().enumerate() {
+                rows[(r.y_start - t.bbox.min_y) as usize + i].push(run);
+            }
+        }
+        Set2D::from_rows(t.bbox.min_y, rows)
+    }
+
+    #[test]
+    fn triangulate_problems() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for problem_id in [1, 9, 31, 42, 77] {
+            let hole = load_problem(problem_id).hole;
+            let t = Triangulation::new(&hole);
+            assert_eq!(t.triangles.len(), hole.len() - 2);
+            let area: i64 = t.triangles.iter().map(|tri| poly_area_doubled(tri).abs()).sum();
+            assert_eq!(area, poly_area_doubled(&hole).abs());
+
+            let bbox = BBox::from_pts(&hole);
+            let inside = lattice_set(&t);
+            assert_eq!(inside, Set2D::from_polygon(&hole));
+            let mut expected = HashSet::new();
+            for x in bbox.min_x - 1..=bbox.max_x + 1 {
+                for y in bbox.min_y - 1..=bbox.max_y + 1 {
+                    let pt = Pt::new(x, y);
+                    assert_eq!(t.locate(pt).is_some(), pt_in_poly(pt, &hole), "{:?}", pt);
+                    assert_eq!(inside.contains(pt), pt_in_poly(pt, &hole), "{:?}", pt);
+                    if pt_in_poly(pt, &hole) {
+                        expected.insert(pt);
+                    }
+                }
+            }
+            let points = inside.as_points();
+            assert_eq!(points.len(), expected.len());
+            assert_eq!(points.into_iter().collect::<HashSet<_>>(), expected);
+
+            for _ in 0..100 {
+                assert!(expected.contains(&t.random_point(&mut rng)));
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_sampling() {
+        // Two triangles sharing the diagonal (0, 0)-(2, 2) with 3 points on it.
+        let hole = [Pt::new(0, 0), Pt::new(2, 0), Pt::new(2, 2), Pt::new(0, 2)];
+        let t = Triangulation::new(&hole);
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut cnt = std::collections::HashMap::new();
+        let n = 90_000;
+        for _ in 0..n {
+            *cnt.entry(t.random_point(&mut rng)).or_insert(0) += 1;
+        }
+        assert_eq!(cnt.len(), 9);
+        for &c in cnt.values() {
+            assert!((c as f64 - n as f64 / 9.0).abs() < 500.0, "{:?}", cnt);
+        }
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::path::{Path, PathBuf};
 use crate::prelude::{Problem, Pose};
+use crate::domain_model::ProblemError;
 
 pub fn project_root() -> PathBuf {
     let exe = std::fs::canonicalize(std::env::args().next().unwrap()).unwrap();
@@ -28,20 +29,110 @@ fn project_path_test() {
     assert!(project_path("src/util.rs").exists());
 }
 
+/// Value of `--name X` or `--name=X` on the command line.
+pub fn flag_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let prefix = format!("--{}=", name);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg.strip_prefix("--") == Some(name) {
+            Some(args.get(i + 1).cloned().unwrap_or_default())
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+/// `--seed N` from the command line, or a fresh random seed to record.
+pub fn seed_flag() -> u64 {
+    match flag_value("seed") {
+        Some(s) => s.parse().unwrap_or_else(|_| panic!("--seed {:?}", s)),
+        None => rand::random(),
+    }
+}
+
+/// The official problems, see registry for the rest.
 pub fn all_problem_ids() -> impl Iterator<Item=i32> {
-    1..=132
+    crate::registry::registry().entries.iter().filter(|e| e.official).map(|e| e.id)
+}
+
+// Official problems are found without scanning every problem directory.
+fn problem_path(problem_id: i32) -> PathBuf {
+    let official = project_path(format!("data/problems/{}.problem", problem_id));
+    if official.exists() {
+        return official;
+    }
+    match crate::registry::registry().get(problem_id) {
+        Some(e) => e.path.clone(),
+        None => official,
+    }
 }
 
 pub fn load_problem(problem_id: i32) -> Problem {
-    let path = project_path(format!("data/problems/{}.problem", problem_id));
+    let path = problem_path(problem_id);
     let data = std::fs::read(path).unwrap();
     let problem: Problem = serde_json::from_slice(&data).unwrap();
     problem
 }
 
+#[derive(Debug)]
+pub enum LoadProblemError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(Vec<ProblemError>),  // fatal ones only
+}
+
+impl std::fmt::Display for LoadProblemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadProblemError::Io(e) => write!(f, "{}", e),
+            LoadProblemError::Json(e) => write!(f, "bad JSON: {}", e),
+            LoadProblemError::Invalid(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "invalid problem: {}", errors.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadProblemError {}
+
+/// Loads and validates a problem file.
+pub fn try_load_problem_file(path: impl AsRef<Path>) -> Result<Problem, LoadProblemError> {
+    let data = std::fs::read(path).map_err(LoadProblemError::Io)?;
+    let problem: Problem = serde_json::from_slice(&data).map_err(LoadProblemError::Json)?;
+    if let Err(errors) = problem.validate() {
+        let fatal: Vec<ProblemError> = errors.into_iter().filter(|e| e.is_fatal()).collect();
+        if !fatal.is_empty() {
+            return Err(LoadProblemError::Invalid(fatal));
+        }
+    }
+    Ok(problem)
+}
+
+pub fn try_load_problem(problem_id: i32) -> Result<Problem, LoadProblemError> {
+    try_load_problem_file(problem_path(problem_id))
+}
+
 pub fn store_solution(problem_id: i32, solution: &Pose) {
     let path = format!("outputs/sol_{}.json", problem_id);
     let data = serde_json::to_vec(&solution).unwrap();
     std::fs::write(project_path(&path), data).unwrap();
     eprintln!("solution saved to {}", path);
 }
+
+#[cfg(test)]
+#[test]
+fn test_try_load_problem() {
+    assert!(try_load_problem(1).is_ok());
+    assert!(matches!(try_load_problem(100_000), Err(LoadProblemError::Io(_))));
+    assert!(matches!(try_load_problem_file(project_path("Cargo.toml")), Err(LoadProblemError::Json(_))));
+    let path = std::env::temp_dir().join("icfpc2021_bad.problem");
+    std::fs::write(&path, r#"{"hole": [[0, 0], [1, 0]], "epsilon": 0, "bonuses": [],
+        "figure": {"vertices": [[0, 0], [0, 1]], "edges": [[0, 1], [1, 2]]}}"#).unwrap();
+    match try_load_problem_file(&path) {
+        Err(e @ LoadProblemError::Invalid(_)) => assert_eq!(e.to_string(),
+            "invalid problem: hole has 2 vertices; figure edge 1 refers to vertex 2 that doesn't exist"),
+        r => panic!("{:?}", r.map(|_| ())),
+    }
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,264 @@
+// Lattice points of the hole visible from a point of the hole, usually a corner.
+// Exact: p is in visible_from(index, c, ..) iff segment_in_poly((c, p), hole).
+//
+// Rays from c through hole vertices split its interior angle
+// into sectors. Inside a sector every ray leaves the hole through the same
+// edge, so the visible part of the open sector is the wedge cut by that edge.
+// Lattice points on the rays themselves are checked one by one.
+
+use std::cmp::Ordering;
+use crate::prelude::*;
+use crate::geom::{gcd, orientation, poly_edges, BBox};
+use crate::hole_index::HoleIndex;
+use crate::rle::Set2D;
+
+// Counterclockwise angle from base to v, as a comparable key.
+fn cmp_angle(base: Pt, u: Pt, v: Pt) -> Ordering {
+    let half = |v: Pt| {
+        let c = base.cross(v);
+        !(c > 0 || c == 0 && base.x * v.x + base.y * v.y > 0)
+    };
+    half(u).cmp(&half(v)).then_with(|| 0.cmp(&u.cross(v)))
+}
+
+// Restricts [lo, hi] to integer x with a * x + k > 0 (or >= 0 if not strict).
+fn restrict(lo: &mut i64, hi: &mut i64, a: i64, k: i64, strict: bool) {
+    match a.cmp(&0) {
+        Ordering::Greater => {
+            let lo2 = if strict {
+                (-k).div_euclid(a) + 1
+            } else {
+                -k.div_euclid(a)
+            };
+            *lo = (*lo).max(lo2);
+        }
+        Ordering::Less => {
+            let hi2 = if strict {
+                -(-k).div_euclid(-a) - 1
+            } else {
+                k.div_euclid(-a)
+            };
+            *hi = (*hi).min(hi2);
+        }
+        Ordering::Equal => {
+            if k < 0 || strict && k == 0 {
+                *hi = *lo - 1;
+            }
+        }
+    }
+}
+
+// Smallest k >= 0 with lo <= c + k * step, or c + k * step <= hi
+// if coming from above. None if never.
+fn steps_to_enter(c: i64, step: i64, lo: i64, hi: i64) -> Option<i64> {
+    if c < lo {
+        if step <= 0 { None } else { Some((lo - c + step - 1) / step) }
+    } else if c > hi {
+        if step >= 0 { None } else { Some((c - hi - step - 1) / -step) }
+    } else {
+        Some(0)
+    }
+}
+
+fn on_segment(pt: Pt, (p, q): (Pt, Pt)) -> bool {
+    orientation(p, q, pt) == 0 &&
+    p.x.min(q.x) <= pt.x && pt.x <= p.x.max(q.x) &&
+    p.y.min(q.y) <= pt.y && pt.y <= p.y.max(q.y)
+}
+
+/// Lattice points p of the hole within the bbox
+/// such that segment_in_poly((c, p), hole). Empty if c is not in the hole.
+pub fn visible_from(index: &HoleIndex, c: Pt, bbox: BBox) -> Set2D {
+    let b = match bbox.intersect(&index.bbox) {
+        Some(b) if index.pt_in_hole(c) => b,
+        _ => return Set2D::empty(),
+    };
+    let mut hole = index.hole.clone();
+    let n = hole.len();
+    let area: i64 = poly_edges(&hole).map(|(a, b)| a.cross(b)).sum();
+    if area < 0 {
+        hole.reverse();
+    }
+    // Counterclockwise now, the interior angle at c goes from start to end.
+    // No end means a full turn, c is strictly inside.
+    let (start, end) = match hole.iter().position(|&v| v == c) {
+        Some(k) => (hole[(k + 1) % n] - c, Some(hole[(k + n - 1) % n] - c)),
+        None => match poly_edges(&hole).find(|&e| on_segment(c, e)) {
+            Some((p, q)) => (q - c, Some(p - c)),
+            None => (Pt::new(1, 0), None),
+        }
+    };
+
+    let mut dirs: Vec<Pt> = hole.iter()
+        .map(|&v| v - c)
+        .chain(std::iter::once(start))
+        .filter(|&d| d != Pt::new(0, 0))
+        .filter(|&d| end.is_none_or(|end| cmp_angle(start, d, end) != Ordering::Greater))
+        .collect();
+    dirs.sort_by(|&u, &v| cmp_angle(start, u, v));
+    dirs.dedup_by(|u, v| cmp_angle(start, *u, *v) == Ordering::Equal);
+
+    // Extra rays so that every sector is narrower than a half-plane.
+    let mut rays = vec![dirs[0]];
+    let closing = if end.is_none() { Some(start) } else { None };
+    for &w in dirs[1..].iter().chain(closing.iter()) {
+        loop {
+            let u = *rays.last().unwrap();
+            if u.cross(w) > 0 {
+                break;
+            }
+            rays.push(Pt::new(-u.y, u.x));
+        }
+        rays.push(w);
+    }
+    let num_rays = if end.is_none() { rays.len() - 1 } else { rays.len() };
+
+    let mut rows = vec![vec![]; (b.max_y - b.min_y + 1) as usize];
+    let mut add = |lo: i64, hi: i64, y: i64| rows[(y - b.min_y) as usize].push((lo, hi));
+
+    let in_bbox = |p: Pt| b.min_x <= p.x && p.x <= b.max_x && b.min_y <= p.y && p.y <= b.max_y;
+    if in_bbox(c) {
+        add(c.x, c.x, c.y);
+    }
+    for &d in &rays[..num_rays] {
+        let g = gcd(d.x, d.y);
+        let step = Pt::new(d.x / g, d.y / g);
+        // Visible points of the ray form a prefix, so it's enough
+        // to walk from the first step inside the bbox until leaving it.
+        let k = match (steps_to_enter(c.x, step.x, b.min_x, b.max_x),
+                       steps_to_enter(c.y, step.y, b.min_y, b.max_y)) {
+            (Some(kx), Some(ky)) => kx.max(ky).max(1),
+            _ => continue,
+        };
+        let mut p = Pt::new(c.x + k * step.x, c.y + k * step.y);
+        while in_bbox(p) && index.segment_in_hole((c, p)) {
+            add(p.x, p.x, p.y);
+            p = p + step;
+        }
+    }
+
+    for uw in rays.windows(2) {
+        let (u, w) = (uw[0], uw[1]);
+        let r = u + w;
+        // The first edge hit by the ray c + t * r, t = num / den.
+        let mut best: Option<(i64, i64, Pt, Pt)> = None;
+        for (p, q) in poly_edges(&hole) {
+            if on_segment(c, (p, q)) {
+                continue;
+            }
+            let op = r.cross(p - c);
+            let oq = r.cross(q - c);
+            if op == 0 || oq == 0 || (op > 0) == (oq > 0) {
+                continue;
+            }
+            let e = q - p;
+            let mut num = (p - c).cross(e);
+            let mut den = r.cross(e);
+            if den < 0 {
+                num = -num;
+                den = -den;
+            }
+            if num <= 0 {
+                continue;
+            }
+            if let Some((best_num, best_den, _, _)) = best {
+                if num as i128 * best_den as i128 >= best_num as i128 * den as i128 {
+                    continue;
+                }
+            }
+            best = Some((num, den, p, q));
+        }
+        let (_, _, p, q) = best.expect("ray does not leave the hole");
+        let e = q - p;
+        let side = orientation(p, q, c);
+        assert!(side != 0);
+
+        for y in b.min_y..=b.max_y {
+            let mut lo = b.min_x;
+            let mut hi = b.max_x;
+            // strictly between u and w
+            restrict(&mut lo, &mut hi, -u.y, u.x * (y - c.y) + u.y * c.x, true);
+            restrict(&mut lo, &mut hi, w.y, -c.x * w.y - (y - c.y) * w.x, true);
+            // on the same side of the edge as c
+            restrict(&mut lo, &mut hi,
+                     -e.y * side, (e.x * (y - p.y) + e.y * p.x) * side, false);
+            if lo <= hi {
+                add(lo, hi, y);
+            }
+        }
+    }
+
+    Set2D::from_rows(b.min_y, rows)
+}
+
+/// Lattice points p of the hole such that segment_in_poly((hole[k], p), hole).
+pub fn visible_from_corner(index: &HoleIndex, k: usize) -> Set2D {
+    visible_from(index, index.hole[k], index.bbox)
+}
+
+/// visible_from_corner for every hole vertex.
+pub fn corner_visibility(index: &HoleIndex) -> Vec<Set2D> {
+    (0..index.hole.len()).map(|k| visible_from_corner(index, k)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use crate::geom::segment_in_poly;
+    use super::*;
+
+    fn check(hole: &[Pt], rng: &mut StdRng) {
+        let index = HoleIndex::new(hole);
+        let b = index.bbox;
+        for (k, visible) in corner_visibility(&index).iter().enumerate() {
+            for x in b.min_x - 1..=b.max_x + 1 {
+                for y in b.min_y - 1..=b.max_y + 1 {
+                    let pt = Pt::new(x, y);
+                    assert_eq!(visible.contains(pt), segment_in_poly((hole[k], pt), hole),
+                               "{:?} {:?}", hole[k], pt);
+                }
+            }
+        }
+        // arbitrary points, clipped to random boxes
+        for _ in 0..20 {
+            let c = Pt::new(rng.gen_range(b.min_x..=b.max_x), rng.gen_range(b.min_y..=b.max_y));
+            let x = rng.gen_range(b.min_x - 2..=b.max_x);
+            let y = rng.gen_range(b.min_y - 2..=b.max_y);
+            let clip = BBox {
+                min_x: x,
+                max_x: x + rng.gen_range(0..=b.max_x - b.min_x),
+                min_y: y,
+                max_y: y + rng.gen_range(0..=b.max_y - b.min_y),
+            };
+            let visible = visible_from(&index, c, clip);
+            for x in b.min_x - 1..=b.max_x + 1 {
+                for y in b.min_y - 1..=b.max_y + 1 {
+                    let pt = Pt::new(x, y);
+                    let in_clip = clip.min_x <= x && x <= clip.max_x &&
+                                  clip.min_y <= y && y <= clip.max_y;
+                    assert_eq!(visible.contains(pt), in_clip && segment_in_poly((c, pt), hole),
+                               "{:?} {:?}", c, pt);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_segment_in_poly() {
+        let mut rng = StdRng::seed_from_u64(42);
+        // comb with teeth touching the same line, collinear corners
+        check(&[
+            Pt::new(0, 0), Pt::new(10, 0), Pt::new(10, 10), Pt::new(8, 10),
+            Pt::new(7, 4), Pt::new(6, 10), Pt::new(4, 10), Pt::new(3, 4),
+            Pt::new(2, 10), Pt::new(0, 10), Pt::new(0, 5),
+        ], &mut rng);
+        // clockwise L with a reflex corner
+        check(&[
+            Pt::new(0, 0), Pt::new(0, 9), Pt::new(3, 9), Pt::new(3, 3),
+            Pt::new(9, 3), Pt::new(9, 0),
+        ], &mut rng);
+        for problem_id in [1, 9, 31] {
+            check(&load_problem(problem_id).hole, &mut rng);
+        }
+    }
+}
@@ -1,9 +1,16 @@
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use std::net::TcpListener;
 use crate::dev_server::{serve_forever, Request, ResponseBuilder, HandlerResult};
 use crate::prelude::*;
+use crate::cancel::CancelToken;
+use crate::progress::{ChannelSink, Progress, ProgressEvent, StderrSink};
 use crate::checker::{CheckPoseRequest, check_pose};
 use crate::shake::{ShakeRequest, shake};
+use crate::solver::{SolveRequest, run_solve_request};
 use crate::rotate::{RotateRequest, rotate};
 // use crate::poses_live::{Scraper};
 
@@ -29,6 +36,70 @@ fn viz_server() {
     });
 }
 
+// A shake or solve request with a time limit and an id to cancel it by.
+#[derive(serde::Deserialize)]
+struct Job<T> {
+    #[serde(flatten)]
+    req: T,
+    #[serde(default)] time_limit_ms: Option<u64>,
+    #[serde(default)] job: Option<String>,
+}
+
+fn running_jobs() -> &'static Mutex<HashMap<String, CancelToken>> {
+    static JOBS: OnceLock<Mutex<HashMap<String, CancelToken>>> = OnceLock::new();
+    JOBS.get_or_init(Default::default)
+}
+
+struct JobProgress {
+    rx: Receiver<ProgressEvent>,
+    touched: Instant,  // created or last polled
+}
+
+// Progress events of jobs, until they are done and all events are fetched,
+// or the job is done and nobody asked for them in a while.
+fn job_progress() -> &'static Mutex<HashMap<String, JobProgress>> {
+    static PROGRESS: OnceLock<Mutex<HashMap<String, JobProgress>>> = OnceLock::new();
+    PROGRESS.get_or_init(Default::default)
+}
+
+const UNPOLLED_PROGRESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Unregisters the job when it's done, even if it panics,
+// and forgets the progress of finished jobs nobody polls.
+struct RunningJob<'a>(Option<&'a String>);
+
+impl Drop for RunningJob<'_> {
+    fn drop(&mut self) {
+        if let Some(job) = self.0 {
+            let mut running = running_jobs().lock().unwrap();
+            running.remove(job);
+            job_progress().lock().unwrap().retain(|job, p|
+                running.contains_key(job) || p.touched.elapsed() < UNPOLLED_PROGRESS_TIMEOUT);
+        }
+    }
+}
+
+impl<T> Job<T> {
+    // The token is findable by /api/cancel/<job> while f() runs,
+    // progress events go to /api/progress/<job>.
+    // Err if another request is already running under the same job id.
+    fn run<R>(&self, name: &str, f: impl FnOnce(&T, &CancelToken, &Progress) -> R) -> Result<R, String> {
+        let stop = CancelToken::with_time_limit(self.time_limit_ms.map(Duration::from_millis));
+        let mut progress = Progress::new(name).with_sink(StderrSink::default());
+        if let Some(job) = &self.job {
+            match running_jobs().lock().unwrap().entry(job.clone()) {
+                Entry::Occupied(_) => return Err(format!("job {:?} is already running", job)),
+                Entry::Vacant(e) => { e.insert(stop.clone()); }
+            }
+            let (tx, rx) = std::sync::mpsc::channel();
+            job_progress().lock().unwrap().insert(job.clone(), JobProgress { rx, touched: Instant::now() });
+            progress = progress.with_sink(ChannelSink(tx));
+        }
+        let _running = RunningJob(self.job.as_ref());
+        Ok(f(&self.req, &stop, &progress))
+    }
+}
+
 fn handler(state: &Mutex<ServerState>, req: &Request, resp: ResponseBuilder) -> HandlerResult {
 
     if req.path == "/" {
@@ -62,10 +133,15 @@ fn handler(state: &Mutex<ServerState>, req: &Request, resp: ResponseBuilder) ->
         let problem_id: i32 = problem_id.parse().unwrap();
         let problem = load_problem(problem_id);
 
-        let pose: Pose = serde_json::from_slice(req.body).unwrap();
+        let pose: Pose = match serde_json::from_slice(req.body) {
+            Ok(pose) => pose,
+            Err(e) => return resp.code("400 Bad Request").body(format!("Malformed pose: {}", e)),
+        };
         let check = check_pose(&problem, &pose);
         if !check.valid {
-            return resp.code("200 OK").body("Invalid solution was not submitted");
+            let reasons: Vec<String> = check.violations.iter().map(|v| v.to_string()).collect();
+            return resp.code("200 OK").body(
+                format!("Invalid solution was not submitted: {}", reasons.join("; ")));
         }
         return match crate::db::write_valid_solution_to_db(
                 client, problem_id, &pose, check.dislikes, "visualizer"
@@ -77,26 +153,131 @@ fn handler(state: &Mutex<ServerState>, req: &Request, resp: ResponseBuilder) ->
 
     if req.path == "/api/check_pose" {
         assert_eq!(req.method, "POST");
-        let req: CheckPoseRequest = serde_json::from_slice(req.body).unwrap();
+        let req: CheckPoseRequest = match serde_json::from_slice(req.body) {
+            Ok(req) => req,
+            Err(e) => return resp.code("400 Bad Request").body(format!("Malformed request: {}", e)),
+        };
+        if let Err(errors) = req.problem.validate() {
+            let fatal: Vec<String> = errors.iter().filter(|e| e.is_fatal()).map(|e| e.to_string()).collect();
+            if !fatal.is_empty() {
+                return resp.code("400 Bad Request").body(format!("Invalid problem: {}", fatal.join("; ")));
+            }
+        }
         let r = check_pose(&req.problem, &req.pose);
         return resp.code("200 OK")
             .body(serde_json::to_vec(&r).unwrap());
     }
 
+    // GET for the problem alone, POST a pose to draw it too.
+    if let Some(problem_id) = req.path.strip_prefix("/api/render/") {
+        let problem = match problem_id.parse().map(crate::util::try_load_problem) {
+            Ok(Ok(problem)) => problem,
+            Ok(Err(e)) => return resp.code("404 Not Found").body(e.to_string()),
+            Err(_) => return resp.code("400 Bad Request").body(format!("Wrong problem id {}", problem_id)),
+        };
+        let pose: Option<Pose> = if req.method == "POST" {
+            match serde_json::from_slice(req.body) {
+                Ok(pose) => Some(pose),
+                Err(e) => return resp.code("400 Bad Request").body(format!("Malformed pose: {}", e)),
+            }
+        } else {
+            None
+        };
+        let svg = crate::render::render_svg(&problem, pose.as_ref(), &format!("problem {}", problem_id));
+        return resp.code("200 OK")
+            .header("Content-Type", "image/svg+xml")
+            .body(svg);
+    }
+
     if req.path == "/api/shake" {
         assert_eq!(req.method, "POST");
-        let req: ShakeRequest = serde_json::from_slice(req.body).unwrap();
-        let r = shake(&req);
+        let req: Job<ShakeRequest> = match serde_json::from_slice(req.body) {
+            Ok(req) => req,
+            Err(e) => return resp.code("400 Bad Request").body(format!("Malformed request: {}", e)),
+        };
+        let r = match req.run(&req.req.method, |r, stop, progress| {
+            shake(&ShakeRequest { stop: stop.clone(), progress: progress.clone(), ..r.clone() })
+        }) {
+            Ok(r) => r,
+            Err(e) => return resp.code("409 Conflict").body(e),
+        };
+        return match r {
+            Ok(r) => resp.code("200 OK").body(serde_json::to_vec(&r).unwrap()),
+            Err(e) => resp.code("400 Bad Request").body(e),
+        };
+    }
+
+    if req.path == "/api/solvers" {
+        let solvers: Vec<serde_json::Value> = crate::solver::solvers().iter().map(|e| serde_json::json!({
+            "name": e.name,
+            "about": e.about,
+            "defaults": e.defaults,
+        })).collect();
         return resp.code("200 OK")
-            .body(serde_json::to_vec(&r).unwrap());
+            .body(serde_json::to_vec(&solvers).unwrap());
     }
 
-    if req.path == "/api/rotate" {
+    if req.path == "/api/solve" {
         assert_eq!(req.method, "POST");
-        let req: RotateRequest = serde_json::from_slice(req.body).unwrap();
-        let r = rotate(&req);
+        let req: Job<SolveRequest> = match serde_json::from_slice(req.body) {
+            Ok(req) => req,
+            Err(e) => return resp.code("400 Bad Request").body(format!("Malformed request: {}", e)),
+        };
+        let r = match req.run(&req.req.solver, run_solve_request) {
+            Ok(r) => r,
+            Err(e) => return resp.code("409 Conflict").body(e),
+        };
+        return match r {
+            Ok(r) => resp.code("200 OK").body(serde_json::to_vec(&r).unwrap()),
+            Err(e) => resp.code("400 Bad Request").body(e),
+        };
+    }
+
+    if let Some(job) = req.path.strip_prefix("/api/cancel/") {
+        assert_eq!(req.method, "POST");
+        return match running_jobs().lock().unwrap().get(job) {
+            Some(stop) => {
+                stop.cancel();
+                resp.code("200 OK").body("")
+            }
+            None => resp.code("404 Not Found").body(format!("no running job {:?}", job)),
+        };
+    }
+
+    // Events since the last call. Poll until done, then the job is forgotten.
+    if let Some(job) = req.path.strip_prefix("/api/progress/") {
+        assert_eq!(req.method, "GET");
+        let mut jobs = job_progress().lock().unwrap();
+        let p = match jobs.get_mut(job) {
+            Some(p) => p,
+            None => return resp.code("404 Not Found").body(format!("no job {:?}", job)),
+        };
+        p.touched = Instant::now();
+        let mut events = vec![];
+        let done = loop {
+            match p.rx.try_recv() {
+                Ok(e) => events.push(e),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        if done {
+            jobs.remove(job);
+        }
         return resp.code("200 OK")
-            .body(serde_json::to_vec(&r).unwrap());
+            .body(serde_json::to_vec(&serde_json::json!({"events": events, "done": done})).unwrap());
+    }
+
+    if req.path == "/api/rotate" {
+        assert_eq!(req.method, "POST");
+        let req: RotateRequest = match serde_json::from_slice(req.body) {
+            Ok(req) => req,
+            Err(e) => return resp.code("400 Bad Request").body(format!("Malformed request: {}", e)),
+        };
+        return match rotate(&req) {
+            Ok(r) => resp.code("200 OK").body(serde_json::to_vec(&r).unwrap()),
+            Err(e) => resp.code("400 Bad Request").body(e),
+        };
     }
 
     // // since the server is (almost) down, switch to db
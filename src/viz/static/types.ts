@@ -65,11 +65,23 @@ export interface CheckPoseRequest {
     pose: Pose,
 }
 
+export type Violation =
+    | { kind: "WrongVertexCount", expected: number, actual: number }
+    | { kind: "EdgeTooShort", edge: number, length: number, min_length: number }
+    | { kind: "EdgeTooLong", edge: number, length: number, max_length: number }
+    | { kind: "VertexOutsideHole", vertex: number }
+    | { kind: "EdgeCrossesHole", edge: number, hole_edge: number | null }
+    | { kind: "TooManyBonuses", count: number }
+    | { kind: "InvalidBonus", bonus: string, reason: string }
+    | { kind: "GlobalistLimitExceeded", sum: number, limit: number }
+    | { kind: "CoordinateOutOfRange", vertex: number, pt: Pt };
+
 export interface CheckPoseResponse {
     edges: Pair[],
     edge_statuses: EdgeStatus[],
     dislikes: number,
     valid: boolean,
+    violations: Violation[],
     unlocked: boolean[],
     bonus_globalist_sum: number | null,
 }
@@ -80,6 +92,29 @@ export interface ShakeRequest {
     selected: boolean[],
     method: string,
     param: number,
+    seed?: number,
+    time_limit_ms?: number,
+    job?: string,
+}
+
+export interface ShakeResponse {
+    vertices: GridPt[],
+    stop_reason: "converged" | "timed_out" | "cancelled",
+    seed: number,  // the one used, to replay the shake
+}
+
+export interface ProgressEvent {
+    solver: string,
+    iteration: number,
+    elapsed_ms: number,
+    score: number | null,  // lower is better
+    best: number | null,
+    violations: number | null,
+}
+
+export interface ProgressResponse {
+    events: ProgressEvent[],
+    done: boolean,
 }
 
 export interface RotateRequest {
@@ -88,6 +123,8 @@ export interface RotateRequest {
     selected: boolean[],
     pivot: Pt | null,
     angle: number,
+    reflect: boolean,
+    triple: [number, number, number] | null,
 }
 
 export type World = Problem;
@@ -5,9 +5,9 @@ import {
     WindowPt, CanvasPt, GridPt,
     Pt, Pair, Figure, Problem, Frame, Foci, Pose,
     Actions, CheckPoseRequest, CheckPoseResponse, RotateRequest,
-    ShakeRequest,
+    ShakeRequest, ShakeResponse, ProgressResponse,
     ProblemTgtBonus,
-    SolutionStats
+    SolutionStats, Violation
 } from "./types.js"
 
 // hole, grid, bonus area, draw only on load
@@ -229,6 +229,7 @@ async function main() {
         "bruteforce",
     ];
     let shakerdiv = document.getElementById('shakers') as HTMLDivElement;
+    let shake_job: string | null = null;
     for (let method of shakers) {
         shakerdiv.innerHTML += `<button id="${method}">${method}</button> `;
     };
@@ -236,25 +237,65 @@ async function main() {
         (document.getElementById(method) as HTMLInputElement).onclick = async function () {
             for (let b of shakerdiv.childNodes) (b as HTMLInputElement).disabled = true;
             let shake_param = document.getElementById('shake-param') as HTMLInputElement;
+            shake_job = `${method}-${Date.now()}`;
             let req: ShakeRequest = {
                 problem: problem,
                 vertices: pose.vertices,
                 selected,
                 method,
                 param: parseInt(shake_param.value),
+                job: shake_job,
             };
-            let r = await fetch('/api/shake', {
+            let r_promise = fetch('/api/shake', {
                 method: 'POST',
                 body: new Blob([JSON.stringify(req)]),
             });
-            assert(r.ok);
-            pose.vertices = await r.json();
+            watch_progress(shake_job);
+            let r = await r_promise;
+            shake_job = null;
+            if (!r.ok) {
+                console.log(await r.text());
+                for (let b of shakerdiv.childNodes) (b as HTMLInputElement).disabled = false;
+                return;
+            }
+            let res: ShakeResponse = await r.json();
+            pose.vertices = res.vertices;
+            console.log(`${method} param ${req.param} seed ${res.seed}`);
+            if (res.stop_reason != "converged") {
+                console.log(`${method} stopped: ${res.stop_reason}`);
+            }
             assert(pose.vertices.length == problem.figure.vertices.length);
             for (let b of shakerdiv.childNodes) (b as HTMLInputElement).disabled = false;
             on_figure_change();
         }
     }
 
+    // Polls the job's progress events until it's done, shows the latest one.
+    let shake_progress = document.getElementById('shake-progress') as HTMLSpanElement;
+    async function watch_progress(job: string) {
+        shake_progress.textContent = '';
+        while (true) {
+            await new Promise(resolve => setTimeout(resolve, 500));
+            let r = await fetch('/api/progress/' + job);
+            if (!r.ok) return;
+            let res: ProgressResponse = await r.json();
+            let e = res.events[res.events.length - 1];
+            if (e !== undefined) {
+                shake_progress.textContent =
+                    `#${e.iteration} ${(e.elapsed_ms / 1000).toFixed(1)}s best ${e.best?.toFixed(2) ?? '-'}`;
+            }
+            if (res.done) return;
+        }
+    }
+
+    // The shake returns its best pose so far when stopped.
+    let stop_shake = document.getElementById('stop-shake') as HTMLButtonElement;
+    stop_shake.onclick = async function () {
+        if (shake_job !== null) {
+            await fetch('/api/cancel/' + shake_job, { method: 'POST' });
+        }
+    };
+
     let reset_buttons = document.getElementById('reset-buttons') as HTMLButtonElement;
     reset_buttons.onclick = function () {
         for (let b of shakerdiv.childNodes) (b as HTMLInputElement).disabled = false;
@@ -394,13 +435,21 @@ async function keyboard_handlers(e: KeyboardEvent) {
 
     if (e.code == "KeyM" || e.code == "KeyN") {
         let angle = 0;
+        let triple: [number, number, number] | null = null;
         if (e.code == "KeyM") {
             angle = e.shiftKey ? 90 : 15;
+            if (e.altKey) triple = [4, 3, 5];
         } else {
             angle = e.shiftKey ? -90 : -15;
+            if (e.altKey) triple = [4, -3, 5];
         }
         e.preventDefault();
-        await turn(angle);
+        await turn(angle, false, triple);
+    }
+    if (e.code == "KeyF" && !(e.ctrlKey || e.metaKey)) {
+        e.preventDefault();
+        await turn(0, true, null);
+        return;
     }
 
     let dx = 0;
@@ -651,13 +700,30 @@ function draw_foci() {
 }
 
 
+function describe_violation(v: Violation): string {
+    switch (v.kind) {
+        case "WrongVertexCount": return `${v.actual} vertices instead of ${v.expected}`;
+        case "EdgeTooShort": return `edge ${v.edge} too short by ${v.min_length - v.length}`;
+        case "EdgeTooLong": return `edge ${v.edge} too long by ${v.length - v.max_length}`;
+        case "VertexOutsideHole": return `vertex ${v.vertex} outside`;
+        case "EdgeCrossesHole":
+            return v.hole_edge == null ? `edge ${v.edge} goes outside`
+                                       : `edge ${v.edge} crosses hole edge ${v.hole_edge}`;
+        case "TooManyBonuses": return `${v.count} bonuses`;
+        case "InvalidBonus": return `${v.bonus}: ${v.reason}`;
+        case "GlobalistLimitExceeded": return `globalist ${Math.ceil(v.sum)} > ${v.limit}`;
+        case "CoordinateOutOfRange": return `vertex ${v.vertex} out of range`;
+    }
+}
+
 function show_dislikes_and_bonuses() {
     let txt = document.getElementById("score")! as HTMLParagraphElement;
     txt.innerHTML = "Dislikes: ";
     txt.innerHTML += `${server_check_result.dislikes} `;
     let brace = false;
     if (!server_check_result.valid) {
-        txt.innerHTML += " (not valid";
+        txt.innerHTML += " (not valid: ";
+        txt.innerHTML += server_check_result.violations.map(describe_violation).join(", ");
         brace = true;
     }
     let ul = false;
@@ -811,19 +877,36 @@ function undo() {
     on_figure_change();
 }
 
-async function turn(angle: number) {
+// A Pythagorean rotation keeps vertices on the lattice only around some lattice
+// points, so it turns around a selected vertex instead of the center of mass.
+function selected_vertex_near_center(): Pt | null {
+    let sel = pose.vertices.filter((_, i) => selected[i]);
+    if (sel.length == 0) return null;
+    let cx = sel.reduce((s, v) => s + v[0], 0) / sel.length;
+    let cy = sel.reduce((s, v) => s + v[1], 0) / sel.length;
+    let d2 = (v: Pt) => (v[0] - cx) ** 2 + (v[1] - cy) ** 2;
+    return sel.reduce((best, v) => d2(v) < d2(best) ? v : best);
+}
+
+async function turn(angle: number, reflect: boolean, triple: [number, number, number] | null) {
     let req: RotateRequest = {
         problem: problem,
         vertices: pose.vertices,
         selected,
-        pivot: null,
-        angle: angle
+        pivot: triple == null ? null : selected_vertex_near_center(),
+        angle: angle,
+        reflect: reflect,
+        triple: triple,
     };
     let r = await fetch('/api/rotate', {
         method: 'POST',
         body: new Blob([JSON.stringify(req)]),
     });
-    assert(r.ok);
+    if (!r.ok) {
+        // e.g. a Pythagorean rotation that would move vertices off the lattice
+        console.log(await r.text());
+        return;
+    }
     pose.vertices = await r.json();
     assert(pose.vertices.length == problem.figure.vertices.length);
     on_figure_change();